@@ -0,0 +1,174 @@
+use std::fmt;
+
+/// Everything the user can do in ink, triggered by a key or from the command palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    ScrollUp,
+    ScrollDown,
+    /// jumps to the end of the chat and follows the streamed replies
+    ScrollToBottom,
+    ScrollLeft,
+    ScrollRight,
+    SelectNext,
+    SelectPrev,
+    /// gives the focus back to the chat, Esc
+    Unfocus,
+    PreviousAlternative,
+    NextAlternative,
+    EditMessage,
+    Regenerate,
+    Continue,
+    CopyMessage,
+    CopyLastReply,
+    /// copies the code block n (starting at 1) of the selected message
+    CopyCodeBlock(usize),
+    SaveCodeBlock,
+    SwitchModel,
+    OpenSession,
+    NewSession,
+    ExportSession,
+    ToggleMarkdown,
+    ToggleCompact,
+    ToggleBubbles,
+    CommandPalette,
+    Submit,
+    InsertNewline,
+    ComposeInEditor,
+    SearchHistory,
+    /// lists the keybindings
+    Help,
+}
+
+impl Action {
+    /// every action, in the order they are listed in the command palette
+    pub const ALL: &[Action] = &[
+        Action::CommandPalette,
+        Action::SwitchModel,
+        Action::OpenSession,
+        Action::NewSession,
+        Action::ExportSession,
+        Action::ToggleMarkdown,
+        Action::ToggleCompact,
+        Action::ToggleBubbles,
+        Action::CopyMessage,
+        Action::CopyLastReply,
+        Action::CopyCodeBlock(1),
+        Action::CopyCodeBlock(2),
+        Action::CopyCodeBlock(3),
+        Action::CopyCodeBlock(4),
+        Action::CopyCodeBlock(5),
+        Action::CopyCodeBlock(6),
+        Action::CopyCodeBlock(7),
+        Action::CopyCodeBlock(8),
+        Action::CopyCodeBlock(9),
+        Action::SaveCodeBlock,
+        Action::Regenerate,
+        Action::Continue,
+        Action::EditMessage,
+        Action::PreviousAlternative,
+        Action::NextAlternative,
+        Action::SelectNext,
+        Action::SelectPrev,
+        Action::ScrollUp,
+        Action::ScrollDown,
+        Action::ScrollToBottom,
+        Action::ScrollLeft,
+        Action::ScrollRight,
+        Action::Unfocus,
+        Action::Submit,
+        Action::InsertNewline,
+        Action::ComposeInEditor,
+        Action::SearchHistory,
+        Action::Help,
+        Action::Quit,
+    ];
+
+    pub fn description(&self) -> String {
+        let description = match self {
+            Action::Quit => "Quit",
+            Action::ScrollUp => "Scroll the chat up",
+            Action::ScrollDown => "Scroll the chat down",
+            Action::ScrollToBottom => "Jump to the end and follow new replies",
+            Action::ScrollLeft => "Scroll the selected message left",
+            Action::ScrollRight => "Scroll the selected message right",
+            Action::SelectNext => "Select the next message",
+            Action::SelectPrev => "Select the previous message",
+            Action::Unfocus => "Leave the input or the selected message",
+            Action::PreviousAlternative => "Show the previous alternative of the message",
+            Action::NextAlternative => "Show the next alternative of the message",
+            Action::EditMessage => "Edit the selected prompt",
+            Action::Regenerate => "Regenerate the selected reply",
+            Action::Continue => "Continue the selected reply",
+            Action::CopyMessage => "Copy the selected message",
+            Action::CopyLastReply => "Copy the last reply",
+            Action::CopyCodeBlock(n) => return format!("Copy code block {} of the message", n),
+            Action::SaveCodeBlock => "Save a code block of the message to a file",
+            Action::SwitchModel => "Switch model",
+            Action::OpenSession => "Open a saved session",
+            Action::NewSession => "Start a new session",
+            Action::ExportSession => "Export the session to markdown",
+            Action::ToggleMarkdown => "Toggle markdown rendering",
+            Action::ToggleCompact => "Toggle compact messages without borders",
+            Action::ToggleBubbles => "Toggle right aligned prompt bubbles",
+            Action::CommandPalette => "Open the command palette",
+            Action::Submit => "Send the prompt",
+            Action::InsertNewline => "Insert a new line in the prompt",
+            Action::ComposeInEditor => "Compose the prompt in $EDITOR",
+            Action::SearchHistory => "Search the prompt history",
+            Action::Help => "Show the keybindings",
+        };
+        description.to_string()
+    }
+
+    /// the name of the action in the configuration, ex: "copy_code_block_1"
+    pub fn name(&self) -> String {
+        let name = match self {
+            Action::Quit => "quit",
+            Action::ScrollUp => "scroll_up",
+            Action::ScrollDown => "scroll_down",
+            Action::ScrollToBottom => "scroll_to_bottom",
+            Action::ScrollLeft => "scroll_left",
+            Action::ScrollRight => "scroll_right",
+            Action::SelectNext => "select_next",
+            Action::SelectPrev => "select_prev",
+            Action::Unfocus => "unfocus",
+            Action::PreviousAlternative => "previous_alternative",
+            Action::NextAlternative => "next_alternative",
+            Action::EditMessage => "edit_message",
+            Action::Regenerate => "regenerate",
+            Action::Continue => "continue",
+            Action::CopyMessage => "copy_message",
+            Action::CopyLastReply => "copy_last_reply",
+            Action::CopyCodeBlock(n) => return format!("copy_code_block_{}", n),
+            Action::SaveCodeBlock => "save_code_block",
+            Action::SwitchModel => "switch_model",
+            Action::OpenSession => "open_session",
+            Action::NewSession => "new_session",
+            Action::ExportSession => "export_session",
+            Action::ToggleMarkdown => "toggle_markdown",
+            Action::ToggleCompact => "toggle_compact",
+            Action::ToggleBubbles => "toggle_bubbles",
+            Action::CommandPalette => "command_palette",
+            Action::Submit => "submit",
+            Action::InsertNewline => "insert_newline",
+            Action::ComposeInEditor => "compose_in_editor",
+            Action::SearchHistory => "search_history",
+            Action::Help => "help",
+        };
+        name.to_string()
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL
+            .iter()
+            .find(|action| action.name() == name)
+            .copied()
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.description())
+    }
+}
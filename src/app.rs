@@ -0,0 +1,638 @@
+use crossterm::{
+    event::{DisableMouseCapture, EnableMouseCapture, EventStream},
+    execute,
+};
+use futures_util::StreamExt;
+use log::error;
+use ratatui::{
+    DefaultTerminal, Terminal,
+    backend::Backend,
+    buffer::Buffer,
+    crossterm::event::{Event, KeyEventKind, MouseButton, MouseEvent, MouseEventKind},
+    layout::{Constraint, Layout, Rect},
+    widgets::{StatefulWidget, Widget},
+};
+use std::{
+    io::{self, stdout},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::time;
+use uuid::Uuid;
+
+use crate::{
+    actions::Action,
+    clipboard::Clipboard,
+    conversation::ConversationEvent,
+    editor,
+    keymap::{Context, Keymap},
+    markdown::code_blocks,
+    session::Session,
+    tools::Policy,
+    widgets::{
+        chat::{Chat, ChatView},
+        help::Help,
+        message::MessageState,
+        palette::{Palette, PaletteEvent, PaletteItem},
+        save_dialog::{SaveAction, SaveDialog},
+        status::StatusLine,
+        tool_dialog::{ToolAnswer, ToolDialog},
+    },
+};
+
+/// how to start ink, printed by `--help` and listed in the help overlay
+pub const USAGE: &str = "ink [--session <path>] [--vim] [--help]
+  --session <path>  resume the conversation saved at <path>, or save a new one there
+  --vim             edit the prompt with vim keybindings
+  --help            print this message";
+
+/// how often the status line is refreshed, the spinner turns at this rate
+const TICK_RATE: Duration = Duration::from_millis(100);
+
+/// What woke the event loop up.
+enum Wake {
+    Event(Option<io::Result<Event>>),
+    /// parts of a streamed reply were written to the chat
+    Reply,
+    Tick,
+}
+
+/// The whole screen: the chat, the status line and the overlays, with the keybindings that
+/// drive them. `run` reads the terminal, tests feed the events to `handle_event` and draw on a
+/// `TestBackend`.
+#[derive(Debug)]
+pub struct App<'a> {
+    pub exit: bool,
+    pub chat: Chat,
+    pub selected_id: Option<Uuid>,
+    pub status: StatusLine,
+    pub clipboard: Clipboard,
+    /// modal used to save a code block of the selected message to a file
+    pub save_dialog: Option<SaveDialog<'a>>,
+    /// set when the prompt should be composed in $EDITOR, which needs the terminal
+    pub compose_in_editor: bool,
+    /// the command palette, lists every action
+    pub palette: Option<Palette<Action>>,
+    /// lists the saved sessions
+    pub session_picker: Option<Palette<PathBuf>>,
+    pub keymap: Keymap,
+    /// lists the keybindings
+    pub help: Option<Help>,
+    /// asks whether the call the model wants to make can run
+    pub tool_dialog: Option<ToolDialog>,
+}
+
+impl<'a> App<'a> {
+    pub fn new(chat: Chat, keymap: Keymap) -> Self {
+        Self {
+            chat,
+            exit: false,
+            selected_id: None,
+            status: StatusLine::default(),
+            clipboard: Clipboard::new(),
+            save_dialog: None,
+            compose_in_editor: false,
+            palette: None,
+            session_picker: None,
+            keymap,
+            help: None,
+            tool_dialog: None,
+        }
+    }
+
+    fn exit(&mut self) {
+        self.exit = true
+    }
+
+    /// Waits for a terminal event, a part of a streamed reply or a tick of the timer, and only
+    /// redraws when one of them changed something.
+    pub async fn run(
+        &mut self,
+        term: &mut DefaultTerminal,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut events = EventStream::new();
+        let mut ticks = time::interval(TICK_RATE);
+        let mut redraw = true;
+
+        while !self.exit {
+            if redraw {
+                self.draw(term)?;
+            }
+
+            let wake = tokio::select! {
+                event = events.next() => Wake::Event(event),
+                _ = self.chat.receive() => Wake::Reply,
+                _ = ticks.tick() => Wake::Tick,
+            };
+
+            redraw = match wake {
+                Wake::Event(Some(event)) => {
+                    self.handle_event(event?)?;
+                    // every part received while handling the event is shown in the same frame
+                    self.chat.receive_pending();
+                    true
+                }
+                Wake::Event(None) => {
+                    self.exit();
+                    false
+                }
+                Wake::Reply => true,
+                Wake::Tick => self.status.tick(),
+            };
+
+            redraw |= self.show_notifications();
+
+            if self.compose_in_editor {
+                self.compose_in_editor = false;
+                // the editor reads the terminal, the stream would steal its input
+                drop(events);
+                self.compose_in_editor(term)?;
+                events = EventStream::new();
+                redraw = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Hands the terminal over to $VISUAL/$EDITOR to edit the content of the textarea and
+    /// loads the edited text back once the editor exits.
+    fn compose_in_editor(&mut self, term: &mut DefaultTerminal) -> io::Result<()> {
+        let text = self.chat.textarea.text();
+
+        execute!(stdout(), DisableMouseCapture)?;
+        ratatui::restore();
+
+        let edited = editor::edit(&text);
+
+        *term = ratatui::init();
+        execute!(stdout(), EnableMouseCapture)?;
+        term.clear()?;
+
+        match edited {
+            Ok(edited) => self.chat.textarea.set_text(&edited),
+            Err(e) => {
+                error!("Failed to compose in {}: {}", editor::editor_command(), e);
+                self.status.notify(format!("Editor failed: {}", e));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Draws a frame on `term` with the selected item focused.
+    pub fn draw<B: Backend>(&mut self, term: &mut Terminal<B>) -> io::Result<()> {
+        self.status.sync(&self.chat);
+        self.define_selected_item();
+        term.draw(|frame| frame.render_widget(&mut *self, frame.area()))?;
+        // the chat moves the selection to the next visible message when it scrolls out
+        if self
+            .selected_id
+            .is_some_and(|id| id != self.chat.textarea.id)
+        {
+            self.selected_id = self.chat.selected_message();
+        }
+        Ok(())
+    }
+
+    /// Moves the notification and the failed requests of the chat to the status line, returns
+    /// true when something was shown.
+    pub fn show_notifications(&mut self) -> bool {
+        let mut shown = false;
+
+        if let Some(notification) = self.chat.notification.take() {
+            self.status.notify(notification);
+            shown = true;
+        }
+        for event in self.chat.take_events() {
+            match event {
+                ConversationEvent::ReplyFailed(_, _) => {
+                    self.status.notify("The request failed");
+                    shown = true;
+                }
+                ConversationEvent::ConfirmTool(_) => {
+                    if let Some((_, call)) = self.chat.conversation.pending_call.as_ref() {
+                        self.tool_dialog = Some(ToolDialog::new(call.clone()));
+                        shown = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        shown
+    }
+
+    fn define_selected_item(&mut self) {
+        self.chat.textarea.is_selected = self.selected_id == Some(self.chat.textarea.id);
+        self.chat.select_only(self.selected_id);
+    }
+
+    fn select_alternative(&mut self, forward: bool) {
+        let new_id = self.chat.select_alternative(forward);
+        if self.selected_id.is_some() && new_id.is_some() {
+            self.selected_id = new_id;
+        }
+    }
+
+    fn regenerate_selected(&mut self) {
+        if let Some(id) = self.chat.selected_message()
+            && self.chat.regenerate(&id, None)
+            && self.selected_id.is_some()
+        {
+            self.selected_id = self.chat.conversation.generating_id;
+        }
+    }
+
+    fn continue_selected(&mut self) {
+        if let Some(id) = self.chat.selected_message() {
+            self.chat.conversation.continue_message(&id);
+        }
+    }
+
+    fn copy(&mut self, text: &str, what: &str) {
+        match self.clipboard.copy(text) {
+            Ok(()) => self.status.notify(format!("Copied {}", what)),
+            Err(e) => {
+                error!("Failed to copy: {}", e);
+                self.status.notify(format!("Failed to copy {}", what));
+            }
+        }
+    }
+
+    fn copy_message(&mut self, id: Option<Uuid>, what: &str) {
+        if let Some(text) = id
+            .and_then(|id| self.chat.conversation.session.tree.get(&id))
+            .map(|msg| msg.text.clone())
+        {
+            self.copy(&text, what);
+        }
+    }
+
+    /// copies the code block `n` (starting at 1) of the selected message
+    fn copy_code_block(&mut self, n: usize) {
+        let Some(msg) = self
+            .chat
+            .selected_message()
+            .and_then(|id| self.chat.conversation.session.tree.get(&id))
+        else {
+            return;
+        };
+
+        match code_blocks(&msg.text).get(n - 1) {
+            Some(block) => {
+                let code = block.code.clone();
+                self.copy(&code, &format!("code block {}", n));
+            }
+            None => self.status.notify(format!("No code block {}", n)),
+        }
+    }
+
+    fn open_save_dialog(&mut self) {
+        let Some(msg) = self
+            .chat
+            .selected_message()
+            .and_then(|id| self.chat.conversation.session.tree.get(&id))
+        else {
+            return;
+        };
+
+        self.save_dialog = SaveDialog::new(code_blocks(&msg.text));
+        if self.save_dialog.is_none() {
+            self.status.notify("No code blocks in this message");
+        }
+    }
+
+    fn handle_save_dialog_events(&mut self, event: Event) {
+        let Some(dialog) = self.save_dialog.as_mut() else {
+            return;
+        };
+
+        match dialog.handle_events(event, &self.keymap) {
+            SaveAction::None => {}
+            SaveAction::Close => self.save_dialog = None,
+            SaveAction::Saved(path) => {
+                self.status.notify(format!("Saved {}", path.display()));
+                self.save_dialog = None;
+            }
+            SaveAction::Failed(e) => {
+                error!("{}", e);
+                self.status.notify(e);
+                self.save_dialog = None;
+            }
+        }
+    }
+
+    fn handle_tool_dialog_events(&mut self, event: Event) {
+        let Some(dialog) = self.tool_dialog.as_ref() else {
+            return;
+        };
+
+        let conversation = &mut self.chat.conversation;
+        match dialog.handle_events(event, &self.keymap) {
+            ToolAnswer::None => return,
+            ToolAnswer::Allow => conversation.confirm_tool(true),
+            ToolAnswer::AlwaysAllow => {
+                let name = dialog.call.function.name.clone();
+                self.status
+                    .notify(format!("{} runs without asking now", name));
+                conversation.sandbox.policies.insert(name, Policy::Allow);
+                conversation.confirm_tool(true)
+            }
+            ToolAnswer::Refuse => conversation.confirm_tool(false),
+        };
+        self.tool_dialog = None;
+    }
+
+    fn handle_mouse_click_events(&mut self, mouse_event: MouseEvent) {
+        let x = mouse_event.column;
+        let y = mouse_event.row;
+
+        if self.chat.textarea.is_within(x, y) {
+            self.selected_id = Some(self.chat.textarea.id);
+        }
+
+        if let Some(id) = self.chat.message_at(x, y) {
+            self.selected_id = Some(id);
+        }
+    }
+
+    pub fn handle_event(&mut self, event: Event) -> io::Result<()> {
+        // the reply waits for the answer, nothing else can be done meanwhile
+        if self.tool_dialog.is_some() {
+            self.handle_tool_dialog_events(event);
+            return Ok(());
+        }
+
+        if self.save_dialog.is_some() {
+            self.handle_save_dialog_events(event);
+            return Ok(());
+        }
+
+        if self.handle_palette_events(event.clone()) {
+            return Ok(());
+        }
+
+        if let Some(help) = self.help.as_mut() {
+            if help.handle_events(event, &self.keymap) {
+                self.help = None;
+            }
+            return Ok(());
+        }
+
+        if let Event::Mouse(mouse_event) = event {
+            match mouse_event.kind {
+                MouseEventKind::ScrollUp => self.chat.scroll_up(),
+                MouseEventKind::ScrollDown => self.chat.scroll_down(),
+                MouseEventKind::Down(MouseButton::Left) => {
+                    self.handle_mouse_click_events(mouse_event)
+                }
+                _ => {}
+            }
+        }
+
+        let Event::Key(key_event) = event else {
+            return Ok(());
+        };
+
+        if self.chat.textarea.is_selected {
+            // the textarea gets every key that is not an action of the app, like Enter
+            match self.keymap.action(Context::Input, key_event) {
+                Some(Action::Unfocus) if self.chat.captures_esc() => {}
+                Some(
+                    action @ (Action::Unfocus
+                    | Action::CommandPalette
+                    | Action::ComposeInEditor
+                    | Action::Help),
+                ) if key_event.kind == KeyEventKind::Press => {
+                    self.perform(action);
+                    return Ok(());
+                }
+                _ => {}
+            }
+
+            return self.chat.handle_events(event);
+        }
+
+        if key_event.kind == KeyEventKind::Press
+            && let Some(action) = self.keymap.action(self.context(), key_event)
+        {
+            self.perform(action);
+        }
+
+        Ok(())
+    }
+
+    fn open_palette(&mut self) {
+        let items = Action::ALL
+            .iter()
+            .map(|action| PaletteItem {
+                label: action.description(),
+                detail: self.keymap.hint(*action),
+                value: *action,
+            })
+            .collect();
+        self.palette = Some(Palette::new("Command palette", items));
+    }
+
+    fn open_session_picker(&mut self) {
+        let items: Vec<PaletteItem<PathBuf>> = Session::list()
+            .into_iter()
+            .map(|summary| PaletteItem {
+                label: summary.title,
+                detail: summary.modified,
+                value: summary.path,
+            })
+            .collect();
+
+        if items.is_empty() {
+            self.status.notify("No saved sessions");
+            return;
+        }
+        self.session_picker = Some(Palette::new("Open session", items));
+    }
+
+    fn open_session(&mut self, path: &Path) {
+        if let Err(e) = self.chat.conversation.session.save() {
+            error!("Failed to save session: {}", e);
+        }
+
+        match Session::load(path) {
+            Ok(session) => {
+                self.chat.set_session(session);
+                self.selected_id = None;
+                self.status.notify(format!("Opened {}", path.display()));
+            }
+            Err(e) => {
+                error!("Failed to open {}: {}", path.display(), e);
+                self.status.notify(format!("Failed to open session: {}", e));
+            }
+        }
+    }
+
+    /// handles the events of the palettes, returns false when no palette is open
+    fn handle_palette_events(&mut self, event: Event) -> bool {
+        if let Some(palette) = self.palette.as_mut() {
+            match palette.handle_events(event, &self.keymap) {
+                PaletteEvent::None => {}
+                PaletteEvent::Close => self.palette = None,
+                PaletteEvent::Selected(action) => {
+                    self.palette = None;
+                    self.perform(action);
+                }
+            }
+            return true;
+        }
+
+        if let Some(picker) = self.session_picker.as_mut() {
+            match picker.handle_events(event, &self.keymap) {
+                PaletteEvent::None => {}
+                PaletteEvent::Close => self.session_picker = None,
+                PaletteEvent::Selected(path) => {
+                    self.session_picker = None;
+                    self.open_session(&path);
+                }
+            }
+            return true;
+        }
+
+        false
+    }
+
+    /// the keybinding context of the focused component
+    fn context(&self) -> Context {
+        if self.chat.textarea.is_selected {
+            return Context::Input;
+        }
+        match self.chat.selected_message() {
+            Some(_) => Context::Message,
+            None => Context::Global,
+        }
+    }
+
+    fn focus_input(&mut self) {
+        self.selected_id = Some(self.chat.textarea.id);
+    }
+
+    fn selected_message_mut(&mut self) -> Option<&mut MessageState> {
+        let id = self.chat.selected_message()?;
+        self.chat.views.get_mut(&id)
+    }
+
+    fn perform(&mut self, action: Action) {
+        match action {
+            Action::Quit => self.exit(),
+            Action::ScrollUp => self.chat.scroll_up(),
+            Action::ScrollDown => self.chat.scroll_down(),
+            Action::ScrollToBottom => self.chat.scroll_to_bottom(),
+            Action::ScrollLeft => {
+                if let Some(msg) = self.selected_message_mut() {
+                    msg.scroll_left();
+                }
+            }
+            Action::ScrollRight => {
+                if let Some(msg) = self.selected_message_mut() {
+                    msg.scroll_right();
+                }
+            }
+            Action::SelectNext => {
+                self.chat.select_next();
+                self.selected_id = self.chat.selected_message();
+            }
+            Action::SelectPrev => {
+                self.chat.select_prev();
+                self.selected_id = self.chat.selected_message();
+            }
+            Action::Unfocus => {
+                self.selected_id = None;
+                self.chat.editing = None;
+            }
+            Action::PreviousAlternative => self.select_alternative(false),
+            Action::NextAlternative => self.select_alternative(true),
+            Action::EditMessage => {
+                if self.chat.edit_selected() {
+                    self.focus_input();
+                }
+            }
+            Action::Regenerate => self.regenerate_selected(),
+            Action::Continue => self.continue_selected(),
+            Action::CopyMessage => self.copy_message(self.chat.selected_message(), "message"),
+            Action::CopyLastReply => {
+                self.copy_message(self.chat.conversation.last_reply(), "last reply")
+            }
+            Action::CopyCodeBlock(n) => self.copy_code_block(n),
+            Action::SaveCodeBlock => self.open_save_dialog(),
+            Action::SwitchModel => {
+                self.chat.textarea.set_text("/model ");
+                self.chat.textarea.update_completions();
+                self.focus_input();
+            }
+            Action::OpenSession => self.open_session_picker(),
+            Action::NewSession => self.chat.run_command("clear", ""),
+            Action::ExportSession => self.chat.run_command("export", ""),
+            Action::ToggleMarkdown => {
+                self.chat.markdown = !self.chat.markdown;
+                let state = if self.chat.markdown { "on" } else { "off" };
+                self.status.notify(format!("Markdown rendering {}", state));
+            }
+            Action::ToggleCompact => {
+                self.chat.compact = !self.chat.compact;
+                let state = if self.chat.compact { "on" } else { "off" };
+                self.status.notify(format!("Compact messages {}", state));
+            }
+            Action::ToggleBubbles => {
+                self.chat.bubbles = !self.chat.bubbles;
+                let state = if self.chat.bubbles { "on" } else { "off" };
+                self.status.notify(format!("Prompt bubbles {}", state));
+            }
+            Action::CommandPalette => self.open_palette(),
+            Action::Submit => self.chat.submit(),
+            Action::InsertNewline => self.chat.textarea.area.insert_newline(),
+            Action::ComposeInEditor => self.compose_in_editor = true,
+            Action::SearchHistory => {
+                self.chat.open_search();
+                self.focus_input();
+            }
+            Action::Help => {
+                self.help = Some(Help::new(
+                    &self.keymap,
+                    self.context(),
+                    USAGE,
+                    &self.chat.theme,
+                ))
+            }
+        }
+    }
+}
+
+impl<'a> Widget for &mut App<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let layout = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).split(area);
+
+        StatefulWidget::render(ChatView, layout[0], buf, &mut self.chat);
+        self.status.render(layout[1], buf);
+
+        if let Some(dialog) = self.save_dialog.as_mut() {
+            dialog.theme = self.chat.theme;
+            dialog.render(layout[0], buf);
+        }
+
+        if let Some(palette) = self.palette.as_mut() {
+            palette.theme = self.chat.theme;
+            palette.render(layout[0], buf);
+        }
+
+        if let Some(picker) = self.session_picker.as_mut() {
+            picker.theme = self.chat.theme;
+            picker.render(layout[0], buf);
+        }
+
+        if let Some(help) = self.help.as_mut() {
+            help.render(layout[0], buf);
+        }
+
+        if let Some(dialog) = self.tool_dialog.as_mut() {
+            dialog.theme = self.chat.theme;
+            dialog.render(layout[0], buf);
+        }
+    }
+}
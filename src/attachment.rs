@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::Read,
+    path::Path,
+};
+
+/// files bigger than this are not inlined in the prompt
+pub const MAX_ATTACHMENT_BYTES: usize = 256 * 1024;
+
+/// how many bytes are checked when looking for a NUL byte to detect binary files
+const BINARY_CHECK_BYTES: usize = 8 * 1024;
+
+/// A file referenced in a prompt with `@path` or `@path:start-end`. The content is read when
+/// the prompt is submitted so regenerating a reply sends the same file, even if it has changed
+/// on disk since.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Attachment {
+    pub path: String,
+    /// first and last line (starting at 1, inclusive) when only part of the file is attached
+    pub range: Option<(usize, usize)>,
+    pub content: String,
+    /// why the file could not be attached, the content is empty when set
+    pub error: Option<String>,
+}
+
+impl Attachment {
+    /// the short label rendered in place of the content, ex: "src/main.rs:10-40 (31 lines)"
+    pub fn label(&self) -> String {
+        let name = match self.range {
+            Some((start, end)) => format!("{}:{}-{}", self.path, start, end),
+            None => self.path.clone(),
+        };
+
+        match &self.error {
+            Some(e) => format!("{}: {}", name, e),
+            None => format!("{} ({} lines)", name, self.content.lines().count()),
+        }
+    }
+}
+
+/// A `@path` reference found in a prompt.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub path: String,
+    pub range: Option<(usize, usize)>,
+}
+
+/// Finds the `@path` and `@path:start-end` references of `text`. An `@` only starts a
+/// reference at the beginning of a word, so e-mail addresses are ignored.
+pub fn references(text: &str) -> Vec<Reference> {
+    text.split_whitespace()
+        .filter_map(|word| word.strip_prefix('@'))
+        .map(|word| word.trim_end_matches([',', ';', ')', '.', '?', '!']))
+        .filter(|word| !word.is_empty())
+        .map(parse_reference)
+        .collect()
+}
+
+fn parse_reference(word: &str) -> Reference {
+    let parsed = word.rsplit_once(':').and_then(|(path, range)| {
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        let start: usize = start.parse().ok()?;
+        let end: usize = end.parse().ok()?;
+        Some(Reference {
+            path: path.to_string(),
+            range: Some((start.max(1), end.max(start).max(1))),
+        })
+    });
+
+    parsed.unwrap_or_else(|| Reference {
+        path: word.to_string(),
+        range: None,
+    })
+}
+
+/// Reads every file referenced in `text`. References that do not look like a path and do not
+/// exist, like "@everyone", are skipped.
+pub fn resolve(text: &str) -> Vec<Attachment> {
+    references(text)
+        .into_iter()
+        .filter(|r| Path::new(&r.path).exists() || r.path.contains(['/', '.']))
+        .map(read)
+        .collect()
+}
+
+fn read(reference: Reference) -> Attachment {
+    let mut attachment = Attachment {
+        path: reference.path,
+        range: reference.range,
+        content: String::new(),
+        error: None,
+    };
+
+    match read_text(Path::new(&attachment.path)) {
+        Ok(text) => match attachment.range {
+            Some((start, end)) => {
+                let lines: Vec<&str> = text.lines().skip(start - 1).take(end - start + 1).collect();
+                if lines.is_empty() {
+                    attachment.error = Some(String::from("line range out of bounds"));
+                } else {
+                    attachment.range = Some((start, start + lines.len() - 1));
+                    attachment.content = lines.join("\n");
+                }
+            }
+            None => attachment.content = text,
+        },
+        Err(e) => attachment.error = Some(e),
+    }
+
+    attachment
+}
+
+/// Reads the file at `path` if it is a text file under `MAX_ATTACHMENT_BYTES`. Devices and
+/// FIFOs are refused, their size is unknown and reading them could block or never end.
+fn read_text(path: &Path) -> Result<String, String> {
+    let metadata = fs::metadata(path).map_err(|e| e.kind().to_string())?;
+    if metadata.is_dir() {
+        return Err(String::from("is a directory"));
+    }
+    if !metadata.is_file() {
+        return Err(String::from("not a regular file"));
+    }
+
+    let too_large = |len: u64| {
+        format!(
+            "too large ({} KB, max {} KB)",
+            len / 1024,
+            MAX_ATTACHMENT_BYTES / 1024
+        )
+    };
+    if metadata.len() as usize > MAX_ATTACHMENT_BYTES {
+        return Err(too_large(metadata.len()));
+    }
+
+    // the file can grow after its size was read
+    let mut bytes = Vec::new();
+    File::open(path)
+        .and_then(|file| {
+            file.take(MAX_ATTACHMENT_BYTES as u64 + 1)
+                .read_to_end(&mut bytes)
+        })
+        .map_err(|e| e.kind().to_string())?;
+    if bytes.len() > MAX_ATTACHMENT_BYTES {
+        return Err(too_large(bytes.len() as u64));
+    }
+    if bytes.iter().take(BINARY_CHECK_BYTES).any(|b| *b == 0) {
+        return Err(String::from("binary file"));
+    }
+
+    String::from_utf8(bytes).map_err(|_| String::from("binary file"))
+}
+
+/// The text sent to the model for a prompt: the prompt followed by the content of every file
+/// that could be attached.
+pub fn inline(text: &str, attachments: &[Attachment]) -> String {
+    let mut content = text.to_string();
+
+    for attachment in attachments.iter().filter(|a| a.error.is_none()) {
+        let name = match attachment.range {
+            Some((start, end)) => format!("{} (lines {}-{})", attachment.path, start, end),
+            None => attachment.path.clone(),
+        };
+        content.push_str(&format!(
+            "\n\n{}:\n```\n{}\n```",
+            name,
+            attachment.content.trim_end()
+        ));
+    }
+
+    content
+}
+
+/// The paths that complete `prefix`, directories end with a '/'. Hidden files are only listed
+/// when the prefix of the name starts with a dot.
+pub fn complete(prefix: &str) -> Vec<String> {
+    let (dir, name) = match prefix.rfind('/') {
+        Some(i) => (&prefix[..=i], &prefix[i + 1..]),
+        None => ("", prefix),
+    };
+
+    let Ok(entries) = fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return Vec::new();
+    };
+
+    let mut completions: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if !file_name.starts_with(name)
+                || (file_name.starts_with('.') && !name.starts_with('.'))
+            {
+                return None;
+            }
+
+            let suffix = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", dir, file_name, suffix))
+        })
+        .collect();
+
+    completions.sort();
+    completions
+}
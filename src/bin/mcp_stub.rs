@@ -0,0 +1,139 @@
+//! A tiny MCP server for the tests of `ink::mcp`, it speaks JSON-RPC over stdio like the real
+//! ones. It offers three tools, a resource and a prompt:
+//!
+//! - `echo {text}` answers `text`
+//! - `add {a, b}` answers the sum of the numbers
+//! - `fail` answers an error result
+//!
+//! `--no-prompts` leaves the prompts out of the capabilities, `--exit-after <n>` exits after
+//! `n` requests to test a server that dies, `--string-ids` sends the ids back as strings.
+
+use serde_json::{Value, json};
+use std::{
+    env,
+    io::{self, BufRead, Write},
+};
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let prompts = !args.iter().any(|arg| arg == "--no-prompts");
+    let string_ids = args.iter().any(|arg| arg == "--string-ids");
+    let exit_after = args
+        .iter()
+        .position(|arg| arg == "--exit-after")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|n| n.parse::<usize>().ok());
+
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let mut requests = 0;
+
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let Ok(message) = serde_json::from_str::<Value>(&line) else {
+            eprintln!("not JSON: {}", line);
+            continue;
+        };
+        // the notifications have no id and need no answer
+        let Some(id) = message.get("id").cloned() else {
+            continue;
+        };
+        let id = match id {
+            Value::Number(id) if string_ids => Value::String(id.to_string()),
+            id => id,
+        };
+
+        requests += 1;
+        if exit_after.is_some_and(|n| requests > n) {
+            return;
+        }
+
+        let method = message["method"].as_str().unwrap_or_default();
+        let response = match answer(method, &message["params"], prompts) {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => {
+                json!({ "jsonrpc": "2.0", "id": id, "error": { "code": -32601, "message": e } })
+            }
+        };
+        if writeln!(stdout, "{}", response)
+            .and(stdout.flush())
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+fn answer(method: &str, params: &Value, prompts: bool) -> Result<Value, String> {
+    match method {
+        "initialize" => {
+            let mut capabilities = json!({ "tools": {}, "resources": {} });
+            if prompts {
+                capabilities["prompts"] = json!({});
+            }
+            Ok(json!({
+                "protocolVersion": params["protocolVersion"],
+                "capabilities": capabilities,
+                "serverInfo": { "name": "mcp_stub", "version": "1.0.0" },
+            }))
+        }
+        "tools/list" => Ok(json!({
+            "tools": [
+                {
+                    "name": "echo",
+                    "description": "Answer the text.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": { "text": { "type": "string" } },
+                        "required": ["text"],
+                    },
+                },
+                {
+                    "name": "add",
+                    "description": "Add two numbers.",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": { "a": { "type": "number" }, "b": { "type": "number" } },
+                        "required": ["a", "b"],
+                    },
+                },
+                { "name": "fail", "inputSchema": { "type": "object" } },
+            ]
+        })),
+        // two pages, the client has to follow the cursor
+        "resources/list" => match params["cursor"].as_str() {
+            None => Ok(json!({
+                "resources": [{ "uri": "file:///readme.md", "name": "readme" }],
+                "nextCursor": "2",
+            })),
+            Some(_) => Ok(json!({
+                "resources": [{ "uri": "file:///changelog.md", "name": "changelog" }],
+            })),
+        },
+        "prompts/list" => Ok(json!({
+            "prompts": [{ "name": "review", "description": "Review the changes." }]
+        })),
+        "tools/call" => call(&params["name"], &params["arguments"]),
+        _ => Err(format!("Method not found: {}", method)),
+    }
+}
+
+fn call(name: &Value, arguments: &Value) -> Result<Value, String> {
+    let text = |text: String, is_error: bool| json!({ "content": [{ "type": "text", "text": text }], "isError": is_error });
+
+    match name.as_str() {
+        Some("echo") => Ok(text(
+            arguments["text"].as_str().unwrap_or_default().to_string(),
+            false,
+        )),
+        Some("add") => {
+            let sum =
+                arguments["a"].as_f64().unwrap_or(0.0) + arguments["b"].as_f64().unwrap_or(0.0);
+            Ok(text(sum.to_string(), false))
+        }
+        Some("fail") => Ok(text(String::from("the tool failed on purpose"), true)),
+        _ => Err(format!("Unknown tool: {}", name)),
+    }
+}
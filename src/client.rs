@@ -0,0 +1,535 @@
+//! A small async client for the chat API of ollama, usable without the TUI.
+//!
+//! ```no_run
+//! use futures_util::StreamExt;
+//! use ink::client::{ChatEvent, ChatRequest, Client};
+//! use ink::conversation::Role;
+//!
+//! # async fn run() -> ink::client::Result<()> {
+//! let client = Client::new("http://localhost:11434");
+//! let request = ChatRequest::new("llama3.2")
+//!     .with_system("Answer in one sentence.")
+//!     .with_message(Role::User, "Why is the sky blue?");
+//!
+//! let mut events = client.chat(&request).await?;
+//! while let Some(event) = events.next().await {
+//!     match event? {
+//!         ChatEvent::Delta(text) => print!("{}", text),
+//!         ChatEvent::ToolCalls(calls) => println!("\ncalls {:?}", calls),
+//!         ChatEvent::Done(stats) => println!("\n{:.1} tok/s", stats.rate().unwrap_or(0.0)),
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use futures_util::{Stream, StreamExt, future::BoxFuture, stream};
+use log::error;
+use reqwest::StatusCode;
+use reqwest_streams::{
+    JsonStreamResponse,
+    error::{StreamBodyError, StreamBodyKind},
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::{fmt, pin::Pin, time::Duration};
+use tokio_util::codec::LinesCodecError;
+
+use crate::conversation::Role;
+
+/// the longest line of a streamed response, ollama sends one small object per token
+const MAX_LINE_LENGTH: usize = 1024 * 1024;
+
+/// Why a request failed.
+#[derive(Debug)]
+pub enum Error {
+    /// the server could not be reached
+    Http(reqwest::Error),
+    /// the server answered with an error status, with the error message of ollama
+    Status(StatusCode, String),
+    /// the connection was lost while the reply was streamed
+    Interrupted(String),
+    /// a line of the stream is not a valid response
+    Decode(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Http(e) => write!(f, "{}", e),
+            Error::Status(status, message) => write!(f, "{}: {}", status, message),
+            Error::Interrupted(e) => write!(f, "The reply was interrupted: {}", e),
+            Error::Decode(e) => write!(f, "Invalid response: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+impl From<StreamBodyError> for Error {
+    fn from(e: StreamBodyError) -> Self {
+        // the line codec reports the lost connection as a codec error wrapping the io error
+        let lost = e
+            .source()
+            .and_then(|source| source.downcast_ref::<LinesCodecError>())
+            .is_some_and(|source| matches!(source, LinesCodecError::Io(_)));
+        match e.kind() {
+            StreamBodyKind::InputOutputError => Error::Interrupted(e.to_string()),
+            _ if lost => Error::Interrupted(e.to_string()),
+            _ => Error::Decode(e.to_string()),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A message of the request or of the response.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Message {
+    pub role: Role,
+    pub content: String,
+    /// the tools the model asks to call, in the replies
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// the tool whose result a `tool` message is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+}
+
+impl Message {
+    pub fn new(role: Role, content: impl Into<String>) -> Self {
+        Self {
+            role,
+            content: content.into(),
+            tool_calls: Vec::new(),
+            tool_name: None,
+        }
+    }
+}
+
+/// A tool the model may call, described by a JSON schema of its arguments.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ToolSpec {
+    /// always "function"
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: FunctionSpec,
+}
+
+impl ToolSpec {
+    pub fn function(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        parameters: serde_json::Value,
+    ) -> Self {
+        Self {
+            kind: String::from("function"),
+            function: FunctionSpec {
+                name: name.into(),
+                description: description.into(),
+                parameters,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FunctionSpec {
+    pub name: String,
+    pub description: String,
+    /// the JSON schema of the arguments
+    pub parameters: serde_json::Value,
+}
+
+/// A call of a tool requested by the model.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ToolCall {
+    pub function: FunctionCall,
+}
+
+impl ToolCall {
+    pub fn new(name: impl Into<String>, arguments: serde_json::Value) -> Self {
+        Self {
+            function: FunctionCall {
+                name: name.into(),
+                arguments,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FunctionCall {
+    pub name: String,
+    /// an object with the arguments by name
+    #[serde(default)]
+    pub arguments: serde_json::Value,
+}
+
+impl fmt::Display for ToolCall {
+    /// the call as the model wrote it, ex: `read_file {"path":"src/main.rs"}`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.function.name, self.function.arguments)
+    }
+}
+
+/// The generation parameters, None leaves the default of the model.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Options {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+}
+
+/// The body of `POST /api/chat`, built with the `with_` methods.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ChatRequest {
+    pub model: String,
+    pub messages: Vec<Message>,
+    pub options: Options,
+    /// the reply is streamed one token at a time when set, the default
+    pub stream: bool,
+    /// the tools the model may call instead of answering
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ToolSpec>,
+}
+
+impl ChatRequest {
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            messages: Vec::new(),
+            options: Options::default(),
+            stream: true,
+            tools: Vec::new(),
+        }
+    }
+
+    /// appends a message to the history sent to the model
+    pub fn with_message(mut self, role: Role, content: impl Into<String>) -> Self {
+        self.messages.push(Message::new(role, content));
+        self
+    }
+
+    /// appends a reply of the model that asked to call `calls`
+    pub fn with_tool_calls(mut self, content: impl Into<String>, calls: Vec<ToolCall>) -> Self {
+        self.messages.push(Message {
+            tool_calls: calls,
+            ..Message::new(Role::Assistant, content)
+        });
+        self
+    }
+
+    /// appends the result of a call of the tool `name`
+    pub fn with_tool_result(mut self, name: impl Into<String>, result: impl Into<String>) -> Self {
+        self.messages.push(Message {
+            tool_name: Some(name.into()),
+            ..Message::new(Role::Tool, result)
+        });
+        self
+    }
+
+    pub fn with_tools(mut self, tools: Vec<ToolSpec>) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    /// appends a system message, send it first so it applies to the whole conversation
+    pub fn with_system(self, prompt: impl Into<String>) -> Self {
+        self.with_message(Role::System, prompt)
+    }
+
+    pub fn with_temperature(mut self, temperature: Option<f32>) -> Self {
+        self.options.temperature = temperature;
+        self
+    }
+}
+
+/// One line of the response of `POST /api/chat`, the statistics are only in the last one.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChatResponse {
+    pub model: String,
+    pub created_at: String,
+    pub message: Message,
+    pub done: bool,
+    #[serde(flatten)]
+    pub stats: Stats,
+}
+
+impl ChatResponse {
+    /// the events of this line, empty parts of the reply are skipped
+    pub fn events(self) -> Vec<ChatEvent> {
+        let mut events = Vec::new();
+        if !self.message.content.is_empty() {
+            events.push(ChatEvent::Delta(self.message.content));
+        }
+        if !self.message.tool_calls.is_empty() {
+            events.push(ChatEvent::ToolCalls(self.message.tool_calls));
+        }
+        if self.done {
+            events.push(ChatEvent::Done(self.stats));
+        }
+        events
+    }
+}
+
+/// The body of `POST /api/generate`, a single prompt without a conversation.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct GenerateRequest {
+    pub model: String,
+    pub prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    pub options: Options,
+    pub stream: bool,
+}
+
+impl GenerateRequest {
+    pub fn new(model: impl Into<String>, prompt: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            prompt: prompt.into(),
+            system: None,
+            options: Options::default(),
+            stream: true,
+        }
+    }
+
+    pub fn with_system(mut self, prompt: impl Into<String>) -> Self {
+        self.system = Some(prompt.into());
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: Option<f32>) -> Self {
+        self.options.temperature = temperature;
+        self
+    }
+}
+
+/// One line of the response of `POST /api/generate`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GenerateResponse {
+    pub model: String,
+    pub created_at: String,
+    pub response: String,
+    pub done: bool,
+    #[serde(flatten)]
+    pub stats: Stats,
+}
+
+impl GenerateResponse {
+    /// the events of this line, like `ChatResponse::events`
+    pub fn events(self) -> Vec<ChatEvent> {
+        let mut events = Vec::new();
+        if !self.response.is_empty() {
+            events.push(ChatEvent::Delta(self.response));
+        }
+        if self.done {
+            events.push(ChatEvent::Done(self.stats));
+        }
+        events
+    }
+}
+
+/// A model installed on the server, as listed by `GET /api/tags`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Model {
+    pub name: String,
+    /// in bytes
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub modified_at: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Models {
+    models: Vec<Model>,
+}
+
+/// How long the reply took, durations are in nanoseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+pub struct Stats {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_duration: Option<u64>,
+    /// the tokens of the prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_eval_count: Option<u64>,
+    /// the tokens of the reply
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eval_count: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eval_duration: Option<u64>,
+}
+
+impl Stats {
+    /// the tokens of the reply per second, as measured by the server
+    pub fn rate(&self) -> Option<f64> {
+        let count = self.eval_count? as f64;
+        let seconds = Duration::from_nanos(self.eval_duration?).as_secs_f64();
+        (seconds > 0.0).then(|| count / seconds)
+    }
+}
+
+/// What a streamed reply is made of.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatEvent {
+    /// the next part of the reply
+    Delta(String),
+    /// the model asks to call tools, their results are expected in the next request
+    ToolCalls(Vec<ToolCall>),
+    /// the reply is complete
+    Done(Stats),
+}
+
+/// The events of a streamed reply, `ChatEvent::Done` is the last one unless the request fails.
+pub type ChatStream = Pin<Box<dyn Stream<Item = Result<ChatEvent>> + Send>>;
+
+/// the body of the responses of ollama when a request fails
+#[derive(Deserialize, Debug)]
+struct ErrorBody {
+    error: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Version {
+    version: String,
+}
+
+/// Where the replies come from. `Client` asks an ollama server, other implementations can
+/// serve another API or canned replies.
+pub trait Provider: fmt::Debug + Send + Sync {
+    /// the address of the server, shown in the status line
+    fn host(&self) -> &str;
+
+    /// sends `request` and streams the reply, like `Client::chat`
+    fn send(&self, request: ChatRequest) -> BoxFuture<'static, Result<ChatStream>>;
+}
+
+/// A connection to an ollama server.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    /// like "http://localhost:11434"
+    pub host: String,
+}
+
+impl Client {
+    pub fn new(host: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            host: host.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// gives up on requests that take longer than `timeout`, streamed replies included; keeps
+    /// the client without a timeout when the HTTP client cannot be built
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        match reqwest::Client::builder().timeout(timeout).build() {
+            Ok(http) => self.http = http,
+            Err(e) => error!("Failed to set a timeout of {:?}: {}", timeout, e),
+        }
+        self
+    }
+
+    /// the version of the server, also a cheap way to check that it is up
+    pub async fn version(&self) -> Result<String> {
+        let response = self
+            .http
+            .get(format!("{}/api/version", self.host))
+            .send()
+            .await?;
+        let body: Version = check(response).await?.json().await?;
+        Ok(body.version)
+    }
+
+    /// the models installed on the server
+    pub async fn models(&self) -> Result<Vec<Model>> {
+        let response = self
+            .http
+            .get(format!("{}/api/tags", self.host))
+            .send()
+            .await?;
+        let body: Models = check(response).await?.json().await?;
+        Ok(body.models)
+    }
+
+    /// Sends `request` and streams the reply. Fails when the server cannot be reached or
+    /// rejects the request, like when the model does not exist.
+    pub async fn chat(&self, request: &ChatRequest) -> Result<ChatStream> {
+        let response = self
+            .http
+            .post(format!("{}/api/chat", self.host))
+            .json(request)
+            .send()
+            .await?;
+        Ok(events(check(response).await?, ChatResponse::events))
+    }
+
+    /// Sends a single prompt and streams the reply, like `chat`.
+    pub async fn generate(&self, request: &GenerateRequest) -> Result<ChatStream> {
+        let response = self
+            .http
+            .post(format!("{}/api/generate", self.host))
+            .json(request)
+            .send()
+            .await?;
+        Ok(events(check(response).await?, GenerateResponse::events))
+    }
+}
+
+impl Provider for Client {
+    fn host(&self) -> &str {
+        &self.host
+    }
+
+    fn send(&self, request: ChatRequest) -> BoxFuture<'static, Result<ChatStream>> {
+        let client = self.clone();
+        Box::pin(async move { client.chat(&request).await })
+    }
+}
+
+/// Reads the lines of `response` as `T` and turns each of them into events with `events`. A
+/// body ending without a `done` line is reported as `Error::Interrupted`, so the stream always
+/// ends with `ChatEvent::Done` or an error.
+fn events<T>(response: reqwest::Response, events: fn(T) -> Vec<ChatEvent>) -> ChatStream
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    let lines = response.json_nl_stream::<T>(MAX_LINE_LENGTH);
+    let stream = stream::unfold((lines, false), move |(mut lines, ended)| async move {
+        let events = match lines.next().await {
+            Some(Ok(line)) => events(line).into_iter().map(Ok).collect(),
+            Some(Err(e)) => vec![Err(Error::from(e))],
+            None if ended => return None,
+            None => vec![Err(Error::Interrupted(
+                "the server closed the reply before its end".to_string(),
+            ))],
+        };
+        let ended = ended
+            || events
+                .iter()
+                .any(|event| matches!(event, Ok(ChatEvent::Done(_)) | Err(_)));
+        Some((stream::iter(events), (lines, ended)))
+    })
+    .flatten();
+    Box::pin(stream)
+}
+
+/// turns an error status into `Error::Status` with the message of ollama
+async fn check(response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let text = response.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<ErrorBody>(&text)
+        .map(|body| body.error)
+        .unwrap_or(text);
+    Err(Error::Status(status, message))
+}
@@ -0,0 +1,55 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use std::{
+    env, fmt,
+    io::{Result, Write, stdout},
+};
+
+/// Copies text to the clipboard of the terminal using the OSC 52 escape sequence, which also
+/// works over ssh and inside of tmux. When ink is built with the `native-clipboard` feature the
+/// text is copied to the system clipboard as well, for terminals that ignore OSC 52.
+#[derive(Default)]
+pub struct Clipboard {
+    /// kept alive because on some platforms the copied text is lost once it is dropped
+    #[cfg(feature = "native-clipboard")]
+    native: Option<arboard::Clipboard>,
+}
+
+impl fmt::Debug for Clipboard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Clipboard").finish()
+    }
+}
+
+impl Clipboard {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "native-clipboard")]
+            native: arboard::Clipboard::new().ok(),
+        }
+    }
+
+    pub fn copy(&mut self, text: &str) -> Result<()> {
+        #[cfg(feature = "native-clipboard")]
+        if let Some(native) = self.native.as_mut()
+            && let Err(e) = native.set_text(text)
+        {
+            log::warn!("Failed to copy to the system clipboard: {}", e);
+        }
+
+        let mut stdout = stdout();
+        stdout.write_all(osc52(text).as_bytes())?;
+        stdout.flush()
+    }
+}
+
+/// the escape sequence that sets the clipboard to `text`, wrapped in a passthrough sequence
+/// when running inside of tmux
+pub fn osc52(text: &str) -> String {
+    let sequence = format!("\x1b]52;c;{}\x07", STANDARD.encode(text));
+
+    if env::var_os("TMUX").is_some() {
+        return format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"));
+    }
+
+    sequence
+}
@@ -0,0 +1,253 @@
+use std::path::PathBuf;
+
+use crate::{session::Session, widgets::chat::Chat};
+
+/// What a command reports back, shown in the status line.
+pub type CommandResult = Result<String, String>;
+
+/// Runs a command with the text typed after its name, the arguments have already been checked
+/// by `Command::validate`.
+pub type Handler = fn(&mut Chat, &str) -> CommandResult;
+
+/// A slash command of the input box, ex: `/set temperature 0.2`.
+#[derive(Debug)]
+pub struct Command {
+    pub name: &'static str,
+    /// the arguments, as shown in the completion popup and the help
+    pub usage: &'static str,
+    pub description: &'static str,
+    /// checks the arguments before the input is cleared, so a typo can be fixed
+    pub validate: fn(&str) -> Result<(), String>,
+    pub run: Handler,
+}
+
+impl Command {
+    /// the name and the usage, ex: "/model <name>"
+    pub fn synopsis(&self) -> String {
+        format!("/{} {}", self.name, self.usage)
+            .trim_end()
+            .to_string()
+    }
+}
+
+/// Every slash command, adding an entry here is enough to make a new command available.
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "model",
+        usage: "<name>",
+        description: "switch the model used for the next replies",
+        validate: required,
+        run: |chat, args| {
+            chat.conversation.session.settings.model = args.to_string();
+            Ok(format!("Model set to {}", args))
+        },
+    },
+    Command {
+        name: "system",
+        usage: "[prompt]",
+        description: "set the system prompt, clears it without a prompt",
+        validate: any,
+        run: |chat, args| {
+            if args.is_empty() {
+                chat.conversation.session.settings.system = None;
+                return Ok(String::from("System prompt cleared"));
+            }
+            chat.conversation.session.settings.system = Some(args.to_string());
+            Ok(String::from("System prompt set"))
+        },
+    },
+    Command {
+        name: "clear",
+        usage: "",
+        description: "start a new conversation, the current one stays saved",
+        validate: none,
+        run: |chat, _| {
+            chat.conversation
+                .session
+                .save()
+                .map_err(|e| e.to_string())?;
+            let mut session = Session::new();
+            session.settings = chat.conversation.session.settings.clone();
+            chat.set_session(session);
+            Ok(String::from("Started a new conversation"))
+        },
+    },
+    Command {
+        name: "save",
+        usage: "[path]",
+        description: "save the conversation, to `path` from now on when given",
+        validate: any,
+        run: |chat, args| {
+            if !args.is_empty() {
+                chat.conversation.session.path = Some(PathBuf::from(args));
+            }
+            chat.conversation
+                .session
+                .save()
+                .map_err(|e| e.to_string())?;
+            Ok(format!(
+                "Saved {}",
+                chat.conversation.session.path().display()
+            ))
+        },
+    },
+    Command {
+        name: "export",
+        usage: "[path]",
+        description: "write the displayed conversation to a markdown file",
+        validate: any,
+        run: |chat, args| {
+            let path = match args {
+                "" => PathBuf::from(format!("ink-{}.md", chat.conversation.session.id)),
+                path => PathBuf::from(path),
+            };
+            std::fs::write(&path, chat.conversation.session.to_markdown())
+                .map_err(|e| e.to_string())?;
+            Ok(format!("Exported {}", path.display()))
+        },
+    },
+    Command {
+        name: "retry",
+        usage: "",
+        description: "generate a new reply to the last prompt",
+        validate: none,
+        run: |chat, _| {
+            let id = chat
+                .conversation
+                .last_reply()
+                .ok_or("There is no reply to retry")?;
+            if !chat.regenerate(&id, None) {
+                return Err(String::from("The last reply is still being generated"));
+            }
+            Ok(String::from("Regenerating the last reply"))
+        },
+    },
+    Command {
+        name: "mcp",
+        usage: "",
+        description: "show the state of the MCP servers and what they offer",
+        validate: none,
+        run: |chat, _| {
+            let servers = chat.conversation.mcp.summaries();
+            if servers.is_empty() {
+                return Err(String::from("No MCP server is configured"));
+            }
+            let servers: Vec<String> = servers.iter().map(|server| server.to_string()).collect();
+            Ok(servers.join("; "))
+        },
+    },
+    Command {
+        name: "set",
+        usage: "temperature <0-2|default> | tools <on|off>",
+        description: "change a generation setting",
+        validate: |args| parse_setting(args).map(|_| ()),
+        run: |chat, args| {
+            let settings = &mut chat.conversation.session.settings;
+            match parse_setting(args)? {
+                Setting::Temperature(temperature) => {
+                    settings.temperature = temperature;
+                    match temperature {
+                        Some(t) => Ok(format!("Temperature set to {}", t)),
+                        None => Ok(String::from("Temperature set to the model default")),
+                    }
+                }
+                Setting::Tools(tools) => {
+                    settings.tools = tools;
+                    match tools {
+                        true => Ok(String::from("The model can call the local tools")),
+                        false => Ok(String::from("The local tools are disabled")),
+                    }
+                }
+            }
+        },
+    },
+];
+
+/// The settings `/set` can change.
+#[derive(Debug, PartialEq)]
+pub enum Setting {
+    /// None resets to the default temperature of the model
+    Temperature(Option<f32>),
+    /// offers the local tools to the model
+    Tools(bool),
+}
+
+fn parse_setting(args: &str) -> Result<Setting, String> {
+    let mut words = args.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some("temperature"), Some("default"), None) => Ok(Setting::Temperature(None)),
+        (Some("temperature"), Some(value), None) => match value.parse::<f32>() {
+            Ok(t) if (0.0..=2.0).contains(&t) => Ok(Setting::Temperature(Some(t))),
+            _ => Err(format!("Invalid temperature {}, expected 0 to 2", value)),
+        },
+        (Some("temperature"), _, _) => Err(String::from("Usage: /set temperature <0-2|default>")),
+        (Some("tools"), Some("on"), None) => Ok(Setting::Tools(true)),
+        (Some("tools"), Some("off"), None) => Ok(Setting::Tools(false)),
+        (Some("tools"), _, _) => Err(String::from("Usage: /set tools <on|off>")),
+        (Some(name), _, _) => Err(format!("Unknown setting {}", name)),
+        (None, _, _) => Err(String::from(
+            "Usage: /set temperature <0-2|default> | tools <on|off>",
+        )),
+    }
+}
+
+fn required(args: &str) -> Result<(), String> {
+    if args.is_empty() {
+        return Err(String::from("Missing argument"));
+    }
+    Ok(())
+}
+
+fn none(args: &str) -> Result<(), String> {
+    if !args.is_empty() {
+        return Err(String::from("This command takes no argument"));
+    }
+    Ok(())
+}
+
+fn any(_: &str) -> Result<(), String> {
+    Ok(())
+}
+
+pub fn find(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|command| command.name == name)
+}
+
+/// the commands whose name starts with `prefix`
+pub fn complete(prefix: &str) -> Vec<&'static Command> {
+    COMMANDS
+        .iter()
+        .filter(|command| command.name.starts_with(prefix))
+        .collect()
+}
+
+/// What the input box should do with the text typed by the user.
+#[derive(Debug)]
+pub enum Input<'i> {
+    /// a message for the model, "//" at the start of the input is sent as a single '/'
+    Prompt(&'i str),
+    /// a valid command and its arguments
+    Command(&'static Command, &'i str),
+}
+
+/// Splits `/name args` into the command and its arguments and validates them. The error is
+/// meant for the status line and includes the usage of the command.
+pub fn parse(input: &str) -> Result<Input<'_>, String> {
+    if let Some(escaped) = input.strip_prefix("//") {
+        return Ok(Input::Prompt(&input[input.len() - escaped.len() - 1..]));
+    }
+
+    let Some(command) = input.strip_prefix('/') else {
+        return Ok(Input::Prompt(input));
+    };
+
+    let (name, args) = command
+        .split_once(char::is_whitespace)
+        .unwrap_or((command, ""));
+    let args = args.trim();
+
+    let command = find(name).ok_or_else(|| format!("Unknown command /{}", name))?;
+    (command.validate)(args).map_err(|e| format!("{}, usage: {}", e, command.synopsis()))?;
+
+    Ok(Input::Command(command, args))
+}
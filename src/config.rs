@@ -0,0 +1,96 @@
+use serde::Deserialize;
+use std::{collections::HashMap, fs, io::ErrorKind, path::PathBuf};
+
+use crate::{mcp::McpServerConfig, theme::ThemeConfig, tools::ToolsConfig};
+
+/// The keys bound to an action, a single key or a list of keys.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Keys {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Keys {
+    pub fn to_vec(&self) -> Vec<String> {
+        match self {
+            Keys::One(key) => vec![key.clone()],
+            Keys::Many(keys) => keys.clone(),
+        }
+    }
+}
+
+/// How the messages are laid out.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig {
+    /// drops the borders of the messages
+    pub compact: bool,
+    /// right aligns the prompts in chat bubbles
+    pub bubbles: bool,
+    /// shown in the header of the prompts, `$USER` by default
+    pub user_name: Option<String>,
+}
+
+/// The settings of the user, read from `config.toml`. Every section is optional.
+///
+/// ```toml
+/// theme = "solarized"
+///
+/// [display]
+/// compact = true
+/// bubbles = true
+///
+/// [keys.global]
+/// quit = ["q", "Ctrl+C"]
+///
+/// [keys.input]
+/// submit = "Ctrl+S"
+/// insert_newline = "Enter"
+///
+/// [tools.policy]
+/// run_shell = "deny"
+///
+/// [mcp.git]
+/// command = "uvx"
+/// args = ["mcp-server-git"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// the keys of each context by action name, they replace the default keys of the action
+    pub keys: HashMap<String, HashMap<String, Keys>>,
+    /// the name of a built-in theme or of one of `themes`
+    pub theme: Option<String>,
+    /// the themes defined by the user
+    pub themes: HashMap<String, ThemeConfig>,
+    pub display: DisplayConfig,
+    /// where the tools called by the model run and which of them need a confirmation
+    pub tools: ToolsConfig,
+    /// the MCP servers launched at startup, by name
+    pub mcp: HashMap<String, McpServerConfig>,
+}
+
+impl Config {
+    /// the file the configuration of the user is read from
+    pub fn path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("ink")
+            .join("config.toml")
+    }
+
+    /// Reads the configuration at `Config::path`, a missing file is the default configuration.
+    pub fn load() -> Result<Self, String> {
+        let path = Self::path();
+        match fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+}
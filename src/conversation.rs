@@ -0,0 +1,704 @@
+use futures_util::{
+    FutureExt, StreamExt, future,
+    stream::{self, BoxStream, SelectAll},
+};
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    env, fmt,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use uuid::Uuid;
+
+use crate::{
+    attachment::{self, Attachment},
+    client::{self, ChatEvent, ChatRequest, Client, Provider, Stats, ToolCall},
+    mcp::Mcp,
+    session::{GenerationSettings, Session},
+    tools::{self, AuditEntry, Decision, Policy, Sandbox, ToolResult},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+    System,
+    /// a request that failed, shown in the chat but never sent to the model
+    Error,
+    /// the result of a tool called by the model
+    Tool,
+}
+
+impl Role {
+    pub fn to_lower_string(&self) -> String {
+        match self {
+            Role::User => String::from("user"),
+            Role::Assistant => String::from("assistant"),
+            Role::System => String::from("system"),
+            Role::Error => String::from("error"),
+            Role::Tool => String::from("tool"),
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::User => write!(f, "User"),
+            Role::Assistant => write!(f, "Assistant"),
+            Role::System => write!(f, "System"),
+            Role::Error => write!(f, "Error"),
+            Role::Tool => write!(f, "Tool"),
+        }
+    }
+}
+
+/// A message of the conversation, what was said and where it sits in the `MessageTree`. How it
+/// is displayed is up to the widgets, see `widgets::message::MessageState`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ChatMessage {
+    pub id: Uuid,
+    /// depth of the message in the conversation tree
+    pub index: usize,
+    pub role: Role,
+    pub text: String,
+    /// set while the reply is streamed into the message
+    #[serde(skip)]
+    pub generating: bool,
+    /// files referenced with `@path`, rendered as chips instead of their content
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    /// the model that generated the message, None for messages written by the user
+    #[serde(default)]
+    pub model: Option<String>,
+    /// when the message was written, in seconds since the unix epoch
+    #[serde(default)]
+    pub created: Option<u64>,
+    /// the tools the model asked to call in this reply, their results follow as tool messages
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// the tool a tool message is the result of
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+    /// the message this one answers to, None for the first message of the conversation
+    pub parent: Option<Uuid>,
+    /// every alternative that follows this message, see `MessageTree`
+    pub children: Vec<Uuid>,
+    /// the child that is part of the active path
+    pub active_child: usize,
+}
+
+impl ChatMessage {
+    pub fn new(role: Role, text: String) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            index: 0,
+            role,
+            text,
+            generating: false,
+            attachments: Vec::new(),
+            model: None,
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|d| d.as_secs()),
+            tool_calls: Vec::new(),
+            tool_name: None,
+            parent: None,
+            children: Vec::new(),
+            active_child: 0,
+        }
+    }
+
+    /// a message written by the user, with the files it references with `@path` attached
+    pub fn prompt(text: String) -> Self {
+        let attachments = attachment::resolve(&text);
+        Self {
+            attachments,
+            ..Self::new(Role::User, text)
+        }
+    }
+}
+
+/// How many times in a row the model can call tools before it has to answer, a model that
+/// keeps calling tools would never reply otherwise.
+pub const MAX_TOOL_ROUNDS: usize = 8;
+
+/// the ollama server used when `OLLAMA_HOST` is not set
+pub const DEFAULT_HOST: &str = "http://localhost:11434";
+
+/// The address of the ollama server, from `OLLAMA_HOST` like the ollama CLI.
+pub fn default_host() -> String {
+    match env::var("OLLAMA_HOST") {
+        Ok(host) if host.starts_with("http://") || host.starts_with("https://") => {
+            host.trim_end_matches('/').to_string()
+        }
+        Ok(host) if !host.is_empty() => format!("http://{}", host.trim_end_matches('/')),
+        _ => DEFAULT_HOST.to_string(),
+    }
+}
+
+/// How far the reply being streamed is.
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+    pub started: Instant,
+    /// the chunks received so far, ollama streams one token per chunk
+    pub tokens: usize,
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            tokens: 0,
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// tokens per second since the request was sent
+    pub fn rate(&self) -> f64 {
+        let seconds = self.elapsed().as_secs_f64();
+        if seconds == 0.0 {
+            return 0.0;
+        }
+        self.tokens as f64 / seconds
+    }
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What happened to a conversation, taken by the app with `Conversation::take_events`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConversationEvent {
+    /// the prompt `id` was added, its reply is being generated
+    Submitted(Uuid),
+    /// the reply `id` is complete
+    ReplyDone(Uuid),
+    /// the tool message `id` received the result of its call
+    ToolCalled(Uuid),
+    /// the call of the tool message `id` waits for `Conversation::confirm_tool`
+    ConfirmTool(Uuid),
+    /// the request for the reply `id` failed, with the reason
+    ReplyFailed(Uuid, String),
+}
+
+/// the streamed replies, tagged with the message they are written to
+type Replies = SelectAll<BoxStream<'static, (Uuid, client::Result<ChatEvent>)>>;
+
+/// A session and the replies being generated for it, without anything about how it is shown:
+/// the chat widget renders it, but it can be driven on its own. Nothing runs in the background,
+/// the replies only make progress while `receive` is awaited.
+pub struct Conversation {
+    /// the messages are stored in `session.tree`
+    pub session: Session,
+    /// where the replies come from, an ollama server at `default_host` unless set
+    pub provider: Arc<dyn Provider>,
+    /// the message the streamed reply is written to
+    pub generating_id: Option<Uuid>,
+    /// how far the streamed reply is, None when no reply is streaming
+    pub progress: Option<Progress>,
+    /// the tokens per second of the last reply
+    pub last_rate: Option<f64>,
+    /// where the tools run and which of them need a confirmation
+    pub sandbox: Sandbox,
+    /// the MCP servers, their tools are offered with the local ones
+    pub mcp: Mcp,
+    /// the call waiting for `confirm_tool`, with the tool message its result is written to
+    pub pending_call: Option<(Uuid, ToolCall)>,
+    /// the calls of the last reply that have not run yet
+    tool_queue: VecDeque<(Uuid, ToolCall)>,
+    events: Vec<ConversationEvent>,
+    replies: Replies,
+}
+
+impl fmt::Debug for Conversation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Conversation")
+            .field("session", &self.session)
+            .field("provider", &self.provider)
+            .field("generating_id", &self.generating_id)
+            .field("progress", &self.progress)
+            .field("last_rate", &self.last_rate)
+            .field("sandbox", &self.sandbox)
+            .field("mcp", &self.mcp)
+            .field("pending_call", &self.pending_call)
+            .field("tool_queue", &self.tool_queue)
+            .field("events", &self.events)
+            .field("replies", &self.replies.len())
+            .finish()
+    }
+}
+
+impl Conversation {
+    pub fn new(session: Session) -> Self {
+        Self {
+            session,
+            provider: Arc::new(Client::new(default_host())),
+            generating_id: None,
+            progress: None,
+            last_rate: None,
+            sandbox: Sandbox::default(),
+            mcp: Mcp::default(),
+            pending_call: None,
+            tool_queue: VecDeque::new(),
+            events: Vec::new(),
+            replies: SelectAll::new(),
+        }
+    }
+
+    pub fn with_provider(mut self, provider: Arc<dyn Provider>) -> Self {
+        self.provider = provider;
+        self
+    }
+
+    /// replaces the session, the replies still streaming to the previous one are cancelled
+    pub fn set_session(&mut self, session: Session) {
+        self.session = session;
+        self.generating_id = None;
+        self.progress = None;
+        self.replies = SelectAll::new();
+        self.tool_queue.clear();
+        self.pending_call = None;
+    }
+
+    /// the events since the last call, oldest first
+    pub fn take_events(&mut self) -> Vec<ConversationEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// the last reply of the model in the active path
+    pub fn last_reply(&self) -> Option<Uuid> {
+        self.session
+            .tree
+            .active_messages()
+            .iter()
+            .rev()
+            .find(|msg| matches!(msg.role, Role::Assistant | Role::Error))
+            .map(|msg| msg.id)
+    }
+
+    pub fn push_user_message(&mut self, value: String) -> Uuid {
+        let parent = self.session.tree.last();
+        self.session.tree.push(parent, ChatMessage::prompt(value))
+    }
+
+    /// pushes `value` as an alternative to the message `id`
+    pub fn push_edited_message(&mut self, id: &Uuid, value: String) -> Uuid {
+        let parent = self.session.tree.get(id).and_then(|msg| msg.parent);
+        self.session.tree.push(parent, ChatMessage::prompt(value))
+    }
+
+    /// Pushes the prompt `value`, as an alternative to the message `editing` when set, and asks
+    /// for its reply. Returns the id of the prompt.
+    pub fn submit(&mut self, value: String, editing: Option<Uuid>) -> Uuid {
+        let id = match editing {
+            Some(editing) => self.push_edited_message(&editing, value),
+            None => self.push_user_message(value),
+        };
+        self.events.push(ConversationEvent::Submitted(id));
+        self.start_generating();
+        id
+    }
+
+    /// builds the request from the messages of `path`, in order
+    fn generate_request(&self, path: &[Uuid], settings: &GenerationSettings) -> ChatRequest {
+        let mut request =
+            ChatRequest::new(settings.model.clone()).with_temperature(settings.temperature);
+
+        if let Some(system) = settings.system.as_ref() {
+            request = request.with_system(system.clone());
+        }
+        if self.offers_tools(path, settings) {
+            let mut specs = tools::specs();
+            specs.extend(self.mcp.specs());
+            request = request.with_tools(specs);
+        }
+
+        path.iter()
+            .filter_map(|id| self.session.tree.get(id))
+            .filter(|item| item.role != Role::Error)
+            .fold(request, |request, item| {
+                let content = attachment::inline(&item.text, &item.attachments);
+                match item.role {
+                    Role::Tool => request
+                        .with_tool_result(item.tool_name.clone().unwrap_or_default(), content),
+                    _ if !item.tool_calls.is_empty() => {
+                        request.with_tool_calls(content, item.tool_calls.clone())
+                    }
+                    role => request.with_message(role, content),
+                }
+            })
+    }
+
+    /// whether the tools are offered to a reply to `path`, they are not once the model called
+    /// them `MAX_TOOL_ROUNDS` times in a row
+    fn offers_tools(&self, path: &[Uuid], settings: &GenerationSettings) -> bool {
+        settings.tools && self.tool_rounds(path) < MAX_TOOL_ROUNDS
+    }
+
+    /// how many replies of `path` called tools since the last prompt
+    fn tool_rounds(&self, path: &[Uuid]) -> usize {
+        path.iter()
+            .rev()
+            .filter_map(|id| self.session.tree.get(id))
+            .take_while(|msg| msg.role != Role::User)
+            .filter(|msg| !msg.tool_calls.is_empty())
+            .count()
+    }
+
+    /// Asks for a reply to the active path, streamed into a new assistant message. Returns the
+    /// id of that message.
+    pub fn start_generating(&mut self) -> Uuid {
+        let path = self.session.tree.active_path();
+        let settings = self.session.settings.clone();
+        self.generate_reply(path.last().copied(), &path, &settings)
+    }
+
+    /// Asks for a new reply to the same history as the assistant message `id`, the reply is
+    /// added as an alternative to it and its id is returned. `settings` can be used to try a
+    /// different model or temperature, the session settings are used otherwise.
+    pub fn regenerate(&mut self, id: &Uuid, settings: Option<GenerationSettings>) -> Option<Uuid> {
+        let msg = self.session.tree.get(id)?;
+
+        if !matches!(msg.role, Role::Assistant | Role::Error) || msg.generating {
+            return None;
+        }
+
+        let parent = msg.parent;
+        let path = self.session.tree.active_path();
+        let position = path.iter().position(|p| p == id)?;
+
+        let settings = settings.unwrap_or_else(|| self.session.settings.clone());
+        Some(self.generate_reply(parent, &path[..position], &settings))
+    }
+
+    /// Asks the model to keep writing the assistant message `id`, the message is resent as the
+    /// last one of the history so the model uses it as the beginning of its answer.
+    pub fn continue_message(&mut self, id: &Uuid) -> bool {
+        let Some(msg) = self.session.tree.get_mut(id) else {
+            return false;
+        };
+
+        if msg.role != Role::Assistant || msg.generating {
+            return false;
+        }
+
+        let settings = match &msg.model {
+            Some(model) => GenerationSettings {
+                model: model.clone(),
+                ..self.session.settings.clone()
+            },
+            None => self.session.settings.clone(),
+        };
+
+        let path = self.session.tree.active_path();
+        let Some(position) = path.iter().position(|p| p == id) else {
+            return false;
+        };
+
+        if let Some(msg) = self.session.tree.get_mut(id) {
+            msg.generating = true;
+        }
+        let request = self.generate_request(&path[..=position], &settings);
+        self.generating_id = Some(*id);
+        self.send(*id, request);
+        true
+    }
+
+    /// pushes an empty assistant message after `parent` and streams the reply to `path` into it
+    fn generate_reply(
+        &mut self,
+        parent: Option<Uuid>,
+        path: &[Uuid],
+        settings: &GenerationSettings,
+    ) -> Uuid {
+        let request = self.generate_request(path, settings);
+
+        let mut msg = ChatMessage::new(Role::Assistant, String::new());
+        msg.generating = true;
+        msg.model = Some(settings.model.clone());
+        let id = self.session.tree.push(parent, msg);
+        self.generating_id = Some(id);
+
+        self.send(id, request);
+        id
+    }
+
+    /// sends `request` to the provider, its reply is written to the message `id` by `receive`
+    fn send(&mut self, id: Uuid, request: ChatRequest) {
+        self.progress = Some(Progress::new());
+
+        let reply = stream::once(self.provider.send(request))
+            .flat_map(|events| match events {
+                Ok(events) => events,
+                Err(e) => stream::iter([Err(e)]).boxed(),
+            })
+            // nothing is read after an error
+            .scan(false, |failed, event| {
+                if *failed {
+                    return future::ready(None);
+                }
+                *failed = event.is_err();
+                future::ready(Some(event))
+            })
+            .map(move |event| (id, event));
+        self.replies.push(reply.boxed());
+    }
+
+    /// Waits for the next part of a streamed reply, then writes everything received so far to
+    /// the messages. Never returns while no reply is streaming.
+    pub async fn receive(&mut self) {
+        if self.replies.is_empty() {
+            future::pending::<()>().await;
+        }
+        if let Some((id, event)) = self.replies.next().await {
+            self.apply(id, event);
+        }
+        self.receive_pending();
+    }
+
+    /// writes what was received so far without waiting, returns false when there was nothing
+    pub fn receive_pending(&mut self) -> bool {
+        let mut received = false;
+        while let Some(Some((id, event))) = self.replies.next().now_or_never() {
+            self.apply(id, event);
+            received = true;
+        }
+        received
+    }
+
+    /// writes what the request task sent to the message `id`
+    fn apply(&mut self, id: Uuid, event: client::Result<ChatEvent>) {
+        let Some(msg) = self.session.tree.get_mut(&id) else {
+            return;
+        };
+
+        let mut rate = None;
+        match event {
+            Ok(ChatEvent::Delta(text)) => {
+                msg.text.push_str(&text);
+                // the output of a tool is not generated by the model
+                if let Some(progress) = self.progress.as_mut()
+                    && self.generating_id == Some(id)
+                    && msg.role != Role::Tool
+                {
+                    progress.tokens += 1;
+                }
+                return;
+            }
+            Ok(ChatEvent::ToolCalls(calls)) => {
+                msg.tool_calls.extend(calls);
+                return;
+            }
+            Ok(ChatEvent::Done(_)) if msg.role == Role::Tool => {
+                self.events.push(ConversationEvent::ToolCalled(id));
+            }
+            Ok(ChatEvent::Done(stats)) => {
+                self.events.push(ConversationEvent::ReplyDone(id));
+                // the rate measured by the server leaves out the time spent loading the model
+                rate = stats
+                    .rate()
+                    .or(self.progress.map(|progress| progress.rate()));
+            }
+            Err(e) => {
+                error!("Failed to generate a reply: {}", e);
+                msg.role = Role::Error;
+                if !msg.text.is_empty() {
+                    msg.text.push_str("\n\n");
+                }
+                msg.text.push_str(&e.to_string());
+                self.events
+                    .push(ConversationEvent::ReplyFailed(id, e.to_string()));
+            }
+        }
+
+        msg.generating = false;
+        let (role, has_calls, is_last) = (
+            msg.role,
+            !msg.tool_calls.is_empty(),
+            msg.children.is_empty(),
+        );
+        if self.generating_id == Some(id) {
+            self.generating_id = None;
+            self.progress = None;
+            if rate.is_some() {
+                self.last_rate = rate;
+            }
+        }
+        if let Err(e) = self.session.save() {
+            error!("Failed to save session: {}", e);
+        }
+
+        match role {
+            Role::Assistant if has_calls => self.call_tools(&id),
+            // the next call runs once the result of the previous one is written
+            Role::Tool if !self.tool_queue.is_empty() => self.call_next_tool(),
+            // the results of every call are in, the model can go on
+            Role::Tool if is_last => {
+                let path = self.session.tree.path_to(&id);
+                let settings = self.session.settings.clone();
+                self.generate_reply(Some(id), &path, &settings);
+            }
+            _ => {}
+        }
+    }
+
+    /// Pushes a tool message after the reply `id` for each call it asks for, then runs the
+    /// calls one after the other and writes their results to the tool messages. The last
+    /// result sends the whole path back to the model.
+    fn call_tools(&mut self, id: &Uuid) {
+        let path = self.session.tree.path_to(id);
+        let history = &path[..path.len().saturating_sub(1)];
+        let settings = self.session.settings.clone();
+        if !self.offers_tools(history, &settings) {
+            // the calls of a reply that was not offered the tools are not run, the model is
+            // asked once more for an answer, without the tools
+            if settings.tools && self.tool_rounds(history) == MAX_TOOL_ROUNDS {
+                self.generate_reply(Some(*id), &path, &settings);
+            }
+            return;
+        }
+        let Some(msg) = self.session.tree.get(id) else {
+            return;
+        };
+
+        let mut parent = *id;
+        for call in msg.tool_calls.clone() {
+            let mut result = ChatMessage::new(Role::Tool, String::new());
+            result.tool_name = Some(call.function.name.clone());
+            result.generating = true;
+            parent = self.session.tree.push(Some(parent), result);
+            self.tool_queue.push_back((parent, call));
+        }
+
+        self.generating_id = Some(parent);
+        self.call_next_tool();
+    }
+
+    /// runs the next call of the queue, or waits for `confirm_tool` when its policy is ask
+    fn call_next_tool(&mut self) {
+        let Some((id, call)) = self.tool_queue.pop_front() else {
+            return;
+        };
+
+        match self.policy(&call.function.name) {
+            Policy::Allow => self.run_tool(id, call, Decision::Allowed),
+            Policy::Deny => self.skip_tool(id, call, Decision::Denied),
+            Policy::Ask => {
+                self.progress = None;
+                self.pending_call = Some((id, call));
+                self.events.push(ConversationEvent::ConfirmTool(id));
+            }
+        }
+    }
+
+    /// the policy of the tool `name`: the one set by the user, or the one of its MCP server, or
+    /// the default of the local tool
+    fn policy(&self, name: &str) -> Policy {
+        if let Some(policy) = self.sandbox.policies.get(name) {
+            return *policy;
+        }
+        match self.mcp.find(name) {
+            Some((_, _, policy)) => policy,
+            None => self.sandbox.policy(name),
+        }
+    }
+
+    /// Answers the call waiting for a confirmation, it runs when `allow` is set and the model is
+    /// told it was refused otherwise. Returns false when no call is waiting.
+    pub fn confirm_tool(&mut self, allow: bool) -> bool {
+        let Some((id, call)) = self.pending_call.take() else {
+            return false;
+        };
+
+        if allow {
+            self.run_tool(id, call, Decision::Confirmed);
+        } else {
+            self.skip_tool(id, call, Decision::Refused);
+        }
+        true
+    }
+
+    /// runs `call` on a thread of its own and writes its output to the tool message `id`
+    fn run_tool(&mut self, id: Uuid, call: ToolCall, decision: Decision) {
+        self.progress = Some(Progress::new());
+        let sandbox = self.sandbox.clone();
+        let mcp = self.mcp.clone();
+        let audit = tools::audit_path(&self.session.path());
+
+        let result = async move {
+            let started = Instant::now();
+            let timeout = sandbox.timeout;
+            let run = async {
+                let arguments = tools::arguments(&call);
+                if let Some(result) = mcp
+                    .call(&call.function.name, arguments, sandbox.max_output)
+                    .await
+                {
+                    return result;
+                }
+                let call = call.clone();
+                tokio::task::spawn_blocking(move || sandbox.run(&call))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("the tool failed: {}", e)))
+            };
+            let result = tokio::time::timeout(timeout, run)
+                .await
+                .unwrap_or_else(|_| Err(format!("stopped after {}s", timeout.as_secs_f64())));
+
+            let entry = AuditEntry::new(&call, decision, &result).with_duration(started.elapsed());
+            if let Err(e) = entry.append(&audit) {
+                error!("Failed to write the audit log {}: {}", audit.display(), e);
+            }
+            tool_output(result)
+        };
+
+        let events = stream::once(result).flat_map(move |output| {
+            stream::iter([
+                (id, Ok(ChatEvent::Delta(output))),
+                (id, Ok(ChatEvent::Done(Stats::default()))),
+            ])
+        });
+        self.replies.push(events.boxed());
+    }
+
+    /// tells the model the call was not run, the reason is written to the tool message `id`
+    fn skip_tool(&mut self, id: Uuid, call: ToolCall, decision: Decision) {
+        let reason = match decision {
+            Decision::Refused => format!("the user refused to run {}", call.function.name),
+            _ => format!("the tool {} is not allowed", call.function.name),
+        };
+        let result = Err(reason);
+
+        let audit = tools::audit_path(&self.session.path());
+        if let Err(e) = AuditEntry::new(&call, decision, &result).append(&audit) {
+            error!("Failed to write the audit log {}: {}", audit.display(), e);
+        }
+
+        self.replies.push(
+            stream::iter([
+                (id, Ok(ChatEvent::Delta(tool_output(result)))),
+                (id, Ok(ChatEvent::Done(Stats::default()))),
+            ])
+            .boxed(),
+        );
+    }
+}
+
+/// what the model reads of `result`, the errors are sent too so it can try something else
+fn tool_output(result: ToolResult) -> String {
+    match result {
+        Ok(output) => output,
+        Err(e) => format!("Error: {}", e),
+    }
+}
@@ -0,0 +1,42 @@
+use std::{
+    env, fs,
+    io::{Error, Result},
+    process::Command,
+};
+use uuid::Uuid;
+
+/// the editor used when neither $VISUAL nor $EDITOR are set
+pub const DEFAULT_EDITOR: &str = "vi";
+
+/// the command of the user's editor, $VISUAL takes precedence over $EDITOR
+pub fn editor_command() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|cmd| !cmd.trim().is_empty())
+        .unwrap_or_else(|| String::from(DEFAULT_EDITOR))
+}
+
+/// Opens the user's editor on a temporary file containing `text` and returns the content of
+/// the file once the editor exits. The caller has to give the terminal back to the editor
+/// before calling this, see `App::compose_in_editor`.
+pub fn edit(text: &str) -> Result<String> {
+    let path = env::temp_dir().join(format!("ink-prompt-{}.md", Uuid::new_v4()));
+    fs::write(&path, text)?;
+
+    // the command can contain arguments, ex: "code --wait"
+    let command = editor_command();
+    let mut words = command.split_whitespace();
+    let program = words.next().unwrap_or(DEFAULT_EDITOR);
+
+    let status = Command::new(program).args(words).arg(&path).status();
+    let edited = fs::read_to_string(&path);
+    fs::remove_file(&path)?;
+
+    let status = status?;
+    if !status.success() {
+        return Err(Error::other(format!("{} exited with {}", program, status)));
+    }
+
+    Ok(edited?.trim_end().to_string())
+}
@@ -0,0 +1,147 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{Result, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+/// how many prompts are kept, the oldest ones are dropped first
+pub const MAX_HISTORY: usize = 1000;
+
+/// The prompts submitted by the user, across every session. Stored as one JSON string per line
+/// so multi-line prompts survive the round trip.
+#[derive(Debug, Default)]
+pub struct History {
+    pub entries: Vec<String>,
+    /// the entry currently recalled with Up/Down, None when editing a new prompt
+    position: Option<usize>,
+    /// the prompt that was being typed before recalling older ones
+    draft: String,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    /// the file the history of the user is stored in
+    pub fn default_path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("ink")
+            .join("history")
+    }
+
+    /// Loads the history stored at `path`, a missing file is an empty history.
+    pub fn load(path: PathBuf) -> Self {
+        let entries = fs::read_to_string(&path)
+            .map(|text| {
+                text.lines()
+                    .filter_map(|line| serde_json::from_str::<String>(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            entries,
+            path: Some(path),
+            ..Default::default()
+        }
+    }
+
+    pub fn open() -> Self {
+        History::load(History::default_path())
+    }
+
+    /// Adds `prompt` to the history and appends it to the history file. The file is only
+    /// trimmed to `MAX_HISTORY` entries once it holds twice as many.
+    pub fn push(&mut self, prompt: &str) -> Result<()> {
+        self.reset();
+
+        if prompt.trim().is_empty() || self.entries.last().is_some_and(|last| last == prompt) {
+            return Ok(());
+        }
+
+        self.entries.push(prompt.to_string());
+        let Some(path) = self.path.clone() else {
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        writeln!(file, "{}", serde_json::to_string(prompt)?)?;
+
+        if self.entries.len() > 2 * MAX_HISTORY {
+            self.trim(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Keeps the last `MAX_HISTORY` entries of the file at `path`, read again so the prompts
+    /// appended by another ink are kept. The file is replaced at once, a crash leaves either
+    /// the old or the new one.
+    fn trim(&mut self, path: &Path) -> Result<()> {
+        let mut entries = History::load(path.to_path_buf()).entries;
+        entries.drain(..entries.len().saturating_sub(MAX_HISTORY));
+
+        let lines: Vec<String> = entries
+            .iter()
+            .filter_map(|entry| serde_json::to_string(entry).ok())
+            .collect();
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".{}.tmp", process::id()));
+        let temp = path.with_file_name(name);
+        fs::write(&temp, lines.join("\n") + "\n")?;
+        fs::rename(&temp, path)?;
+
+        self.entries = entries;
+        Ok(())
+    }
+
+    /// stops recalling entries, the next `older` starts from the most recent one again
+    pub fn reset(&mut self) {
+        self.position = None;
+        self.draft.clear();
+    }
+
+    /// Recalls the entry before the current one. `current` is the text of the textarea, kept to
+    /// be restored when going back past the most recent entry.
+    pub fn older(&mut self, current: &str) -> Option<&str> {
+        let position = match self.position {
+            Some(0) => return None,
+            Some(position) => position - 1,
+            None => {
+                self.draft = current.to_string();
+                self.entries.len().checked_sub(1)?
+            }
+        };
+
+        self.position = Some(position);
+        self.entries.get(position).map(String::as_str)
+    }
+
+    /// Recalls the entry after the current one, or the draft after the most recent entry.
+    pub fn newer(&mut self) -> Option<&str> {
+        let position = self.position? + 1;
+
+        if position >= self.entries.len() {
+            self.position = None;
+            return Some(&self.draft);
+        }
+
+        self.position = Some(position);
+        self.entries.get(position).map(String::as_str)
+    }
+
+    /// The most recent entry containing `query` that is older than the entry `before`, returns
+    /// its index and the entry.
+    pub fn search(&self, query: &str, before: usize) -> Option<(usize, &str)> {
+        let query = query.to_lowercase();
+        self.entries[..before.min(self.entries.len())]
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, entry)| entry.to_lowercase().contains(&query))
+            .map(|(i, entry)| (i, entry.as_str()))
+    }
+}
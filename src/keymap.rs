@@ -0,0 +1,384 @@
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use crate::{actions::Action, config::Keys};
+
+/// Where the focus is, each context has its own keybindings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Context {
+    /// the chat, when the input does not have the focus
+    Global,
+    /// the input box, only keys that do not insert text should be bound
+    Input,
+    /// a selected message, falls back to the global keys
+    Message,
+    /// the palettes, the dialogs and the other overlays
+    Popup,
+}
+
+impl Context {
+    pub const ALL: &[Context] = &[
+        Context::Global,
+        Context::Message,
+        Context::Input,
+        Context::Popup,
+    ];
+
+    /// the name of the context in the configuration
+    pub fn name(&self) -> &'static str {
+        match self {
+            Context::Global => "global",
+            Context::Input => "input",
+            Context::Message => "message",
+            Context::Popup => "popup",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Context> {
+        Context::ALL
+            .iter()
+            .find(|context| context.name() == name)
+            .copied()
+    }
+}
+
+impl fmt::Display for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A key and its modifiers, written like "q", "Y", "Ctrl+P", "Shift+Tab" or "F1".
+///
+/// Shift is part of the character for character keys, "Y" and "Shift+y" are the same key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let mut modifiers =
+            modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        let code = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            // terminals report Ctrl+P as Ctrl+p
+            KeyCode::Char(c) if modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                KeyCode::Char(c.to_ascii_lowercase())
+            }
+            KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::BackTab
+            }
+            KeyCode::BackTab => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::BackTab
+            }
+            code => code,
+        };
+        Self { code, modifiers }
+    }
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        Key::new(event.code, event.modifiers)
+    }
+}
+
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut parts: Vec<&str> = s.split('+').collect();
+        // "+" and "Ctrl++" bind the plus key
+        if s.ends_with('+') {
+            parts.pop();
+            parts.pop();
+            parts.push("+");
+        }
+        let Some((key, prefix)) = parts.split_last() else {
+            return Err(format!("Invalid key \"{}\"", s));
+        };
+
+        for modifier in prefix {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("Invalid modifier \"{}\" in \"{}\"", modifier, s)),
+            };
+        }
+
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match key.to_lowercase().as_str() {
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                "space" => KeyCode::Char(' '),
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                f if f.starts_with('f') && f[1..].parse::<u8>().is_ok() => {
+                    KeyCode::F(f[1..].parse().unwrap())
+                }
+                _ => return Err(format!("Invalid key \"{}\"", s)),
+            },
+        };
+
+        Ok(Key::new(code, modifiers))
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift+")?;
+        }
+
+        let modified = !self.modifiers.is_empty();
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) if modified => write!(f, "{}", c.to_ascii_uppercase()),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::BackTab => write!(f, "Shift+Tab"),
+            KeyCode::PageUp => write!(f, "PageUp"),
+            KeyCode::PageDown => write!(f, "PageDown"),
+            code => write!(f, "{}", code),
+        }
+    }
+}
+
+/// A key bound to an action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub key: Key,
+    pub action: Action,
+}
+
+/// The keys bound to an action by default, per context.
+const DEFAULTS: &[(Context, Action, &[&str])] = &[
+    (Context::Global, Action::Quit, &["q"]),
+    (Context::Global, Action::ScrollUp, &["k"]),
+    (Context::Global, Action::ScrollDown, &["j"]),
+    (Context::Global, Action::ScrollToBottom, &["G", "End"]),
+    (Context::Global, Action::SelectNext, &["Tab"]),
+    (Context::Global, Action::SelectPrev, &["Shift+Tab"]),
+    (Context::Global, Action::Unfocus, &["Esc"]),
+    (Context::Global, Action::CopyLastReply, &["Y"]),
+    (Context::Global, Action::ToggleMarkdown, &["m"]),
+    (Context::Global, Action::CommandPalette, &["Ctrl+P"]),
+    (Context::Global, Action::Help, &["?"]),
+    (Context::Message, Action::ScrollLeft, &["h"]),
+    (Context::Message, Action::ScrollRight, &["l"]),
+    (Context::Message, Action::PreviousAlternative, &["<"]),
+    (Context::Message, Action::NextAlternative, &[">"]),
+    (Context::Message, Action::EditMessage, &["e"]),
+    (Context::Message, Action::Regenerate, &["r"]),
+    (Context::Message, Action::Continue, &["c"]),
+    (Context::Message, Action::CopyMessage, &["y"]),
+    (Context::Message, Action::CopyCodeBlock(1), &["1"]),
+    (Context::Message, Action::CopyCodeBlock(2), &["2"]),
+    (Context::Message, Action::CopyCodeBlock(3), &["3"]),
+    (Context::Message, Action::CopyCodeBlock(4), &["4"]),
+    (Context::Message, Action::CopyCodeBlock(5), &["5"]),
+    (Context::Message, Action::CopyCodeBlock(6), &["6"]),
+    (Context::Message, Action::CopyCodeBlock(7), &["7"]),
+    (Context::Message, Action::CopyCodeBlock(8), &["8"]),
+    (Context::Message, Action::CopyCodeBlock(9), &["9"]),
+    (Context::Message, Action::SaveCodeBlock, &["s"]),
+    (Context::Input, Action::Submit, &["Enter"]),
+    (Context::Input, Action::InsertNewline, &["Ctrl+N"]),
+    (Context::Input, Action::ComposeInEditor, &["Ctrl+G"]),
+    (Context::Input, Action::SearchHistory, &["Ctrl+R"]),
+    (Context::Input, Action::CommandPalette, &["Ctrl+P"]),
+    (Context::Input, Action::Unfocus, &["Esc"]),
+    (Context::Input, Action::Help, &["F1"]),
+    (Context::Popup, Action::Unfocus, &["Esc"]),
+    (Context::Popup, Action::Submit, &["Enter"]),
+    (
+        Context::Popup,
+        Action::SelectNext,
+        &["Down", "Tab", "Ctrl+N"],
+    ),
+    (
+        Context::Popup,
+        Action::SelectPrev,
+        &["Up", "Shift+Tab", "Ctrl+P"],
+    ),
+];
+
+/// The keybindings of every context, the defaults with the keys of the configuration on top.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: HashMap<Context, Vec<Binding>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Self {
+            bindings: HashMap::new(),
+        };
+        for (context, action, keys) in DEFAULTS {
+            let keys = keys.iter().map(|key| key.parse().unwrap()).collect();
+            keymap.bind(*context, *action, keys);
+        }
+        keymap
+    }
+}
+
+impl Keymap {
+    /// Builds the keymap from the `[keys.<context>]` sections of the configuration, the keys
+    /// given for an action replace its default keys in that context, an empty list unbinds it.
+    /// Fails on unknown contexts, actions or keys, and when a key ends up bound to two actions.
+    pub fn from_config(config: &HashMap<String, HashMap<String, Keys>>) -> Result<Self, String> {
+        let mut keymap = Self::default();
+
+        for (context_name, actions) in config {
+            let context = Context::from_name(context_name)
+                .ok_or_else(|| format!("Unknown keybinding context \"{}\"", context_name))?;
+
+            for (action_name, keys) in actions {
+                let action = Action::from_name(action_name)
+                    .ok_or_else(|| format!("Unknown action \"{}\"", action_name))?;
+                let keys = keys
+                    .to_vec()
+                    .iter()
+                    .map(|key| key.parse())
+                    .collect::<Result<Vec<Key>, String>>()?;
+                keymap.bind(context, action, keys);
+            }
+        }
+
+        let conflicts = keymap.conflicts();
+        if !conflicts.is_empty() {
+            return Err(format!("Conflicting keybindings: {}", conflicts.join("; ")));
+        }
+        Ok(keymap)
+    }
+
+    /// replaces the keys of `action` in `context`
+    pub fn bind(&mut self, context: Context, action: Action, keys: Vec<Key>) {
+        let bindings = self.bindings.entry(context).or_default();
+        bindings.retain(|binding| binding.action != action);
+        bindings.extend(keys.into_iter().map(|key| Binding { key, action }));
+    }
+
+    /// the bindings of `context` alone, without the ones it falls back to
+    pub fn bindings(&self, context: Context) -> &[Binding] {
+        self.bindings
+            .get(&context)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// the contexts whose keys are also active in `context`, starting with `context` itself
+    pub fn layers(context: Context) -> &'static [Context] {
+        match context {
+            Context::Global => &[Context::Global],
+            Context::Input => &[Context::Input],
+            Context::Message => &[Context::Message, Context::Global],
+            Context::Popup => &[Context::Popup],
+        }
+    }
+
+    /// the action bound to `key` in `context`
+    pub fn action(&self, context: Context, key: KeyEvent) -> Option<Action> {
+        let key = Key::from(key);
+        Self::layers(context).iter().find_map(|context| {
+            self.bindings(*context)
+                .iter()
+                .find(|binding| binding.key == key)
+                .map(|binding| binding.action)
+        })
+    }
+
+    /// the keys bound to `action` in `context`
+    pub fn keys(&self, context: Context, action: Action) -> Vec<Key> {
+        self.bindings(context)
+            .iter()
+            .filter(|binding| binding.action == action)
+            .map(|binding| binding.key)
+            .collect()
+    }
+
+    /// the keys of `action` outside of the popups, as shown in the command palette
+    pub fn hint(&self, action: Action) -> String {
+        let mut keys: Vec<String> = Vec::new();
+        for context in [Context::Global, Context::Message, Context::Input] {
+            for key in self.keys(context, action) {
+                let key = key.to_string();
+                if !keys.contains(&key) {
+                    keys.push(key);
+                }
+            }
+        }
+        keys.join(", ")
+    }
+
+    /// describes every key bound to two different actions in the same context, including the
+    /// contexts it falls back to
+    pub fn conflicts(&self) -> Vec<String> {
+        let mut conflicts = Vec::new();
+
+        for context in Context::ALL {
+            let mut seen: Vec<(Key, Action, Context)> = Vec::new();
+            for layer in Self::layers(*context) {
+                for binding in self.bindings(*layer) {
+                    let conflict = seen
+                        .iter()
+                        .find(|(key, action, _)| *key == binding.key && *action != binding.action);
+                    let conflict = match conflict {
+                        Some((_, action, seen_in)) if seen_in == layer => format!(
+                            "{} is bound to both {} and {} in {}",
+                            binding.key,
+                            action.name(),
+                            binding.action.name(),
+                            layer
+                        ),
+                        Some((_, action, seen_in)) => format!(
+                            "{} is bound to {} in {} and to {} in {}",
+                            binding.key,
+                            action.name(),
+                            seen_in,
+                            binding.action.name(),
+                            layer
+                        ),
+                        None => {
+                            seen.push((binding.key, binding.action, *layer));
+                            continue;
+                        }
+                    };
+                    // the global conflicts are found again through the message context
+                    if !conflicts.contains(&conflict) {
+                        conflicts.push(conflict);
+                    }
+                }
+            }
+        }
+
+        conflicts
+    }
+}
@@ -1,2 +1,19 @@
+pub mod actions;
+pub mod app;
+pub mod attachment;
+pub mod client;
+pub mod clipboard;
+pub mod commands;
+pub mod config;
+pub mod conversation;
+pub mod editor;
+pub mod history;
+pub mod keymap;
+pub mod markdown;
+pub mod mcp;
+pub mod session;
+pub mod theme;
+pub mod tools;
+pub mod tree;
 pub mod utils;
 pub mod widgets;
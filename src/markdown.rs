@@ -0,0 +1,121 @@
+/// A fenced code block found in a message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeBlock {
+    /// the first word of the fence info string, ex: "rust" for ```rust
+    pub language: Option<String>,
+    /// the file the block is meant to be saved to, taken from the fence info string
+    /// (```rust src/main.rs) or from the line before the fence (`src/main.rs`:)
+    pub filename: Option<String>,
+    pub code: String,
+}
+
+/// Returns every fenced code block (``` or ~~~) of `text`, in order. A block that is never
+/// closed, like the one of a reply that is still streaming, runs until the end of the text.
+pub fn code_blocks(text: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut current: Option<(String, CodeBlock)> = None;
+    let mut previous_line = "";
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+
+        match current.take() {
+            Some((fence, block)) if closes(&fence, trimmed) => {
+                blocks.push(block);
+            }
+            Some((fence, mut block)) => {
+                block.code.push_str(line);
+                block.code.push('\n');
+                current = Some((fence, block));
+            }
+            None => {
+                let Some(fence) = fence_of(trimmed) else {
+                    if !trimmed.is_empty() {
+                        previous_line = trimmed;
+                    }
+                    continue;
+                };
+
+                let info = trimmed[fence.len()..].trim();
+                let (language, filename) = parse_info(info);
+                let block = CodeBlock {
+                    language,
+                    filename: filename.or_else(|| filename_hint(previous_line)),
+                    code: String::new(),
+                };
+                current = Some((fence, block));
+                previous_line = "";
+            }
+        }
+    }
+
+    if let Some((_, block)) = current {
+        blocks.push(block);
+    }
+
+    blocks
+}
+
+/// the opening fence of the line, at least three backticks or tildes
+fn fence_of(line: &str) -> Option<String> {
+    let marker = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let fence: String = line.chars().take_while(|c| *c == marker).collect();
+    (fence.len() >= 3).then_some(fence)
+}
+
+/// whether `line` closes the block opened by `fence`: a fence of the same character, at least
+/// as long and without an info string
+fn closes(fence: &str, line: &str) -> bool {
+    fence_of(line).is_some_and(|closing| {
+        closing.starts_with(&fence[..1]) && closing.len() >= fence.len() && line.trim() == closing
+    })
+}
+
+/// splits the info string of a fence into the language and the file name, accepts
+/// "rust", "rust src/main.rs", "rust:src/main.rs" and "rust title=src/main.rs"
+fn parse_info(info: &str) -> (Option<String>, Option<String>) {
+    let mut words = info.split_whitespace();
+    let Some(first) = words.next() else {
+        return (None, None);
+    };
+
+    if let Some((language, filename)) = first.split_once(':') {
+        return (Some(language.to_string()), looks_like_path(filename));
+    }
+
+    let filename = words.find_map(|word| {
+        let value = word
+            .split_once('=')
+            .map(|(_, value)| value)
+            .unwrap_or(word)
+            .trim_matches(|c| c == '"' || c == '\'');
+        looks_like_path(value)
+    });
+
+    (Some(first.to_string()), filename)
+}
+
+/// the file name mentioned by a line like "`src/main.rs`:", "**File: main.rs**" or "### main.rs"
+fn filename_hint(line: &str) -> Option<String> {
+    let line = line
+        .trim_matches(|c: char| c == '#' || c == '*' || c == '`' || c == ':' || c.is_whitespace());
+    let line = ["File:", "file:", "Filename:", "filename:"]
+        .iter()
+        .find_map(|prefix| line.strip_prefix(prefix))
+        .unwrap_or(line)
+        .trim_matches(|c: char| c == '*' || c == '`' || c == ':' || c.is_whitespace());
+
+    if line.contains(char::is_whitespace) {
+        return None;
+    }
+
+    looks_like_path(line)
+}
+
+fn looks_like_path(value: &str) -> Option<String> {
+    let is_path = !value.is_empty()
+        && (value.contains('/') || value.contains('.'))
+        && !value.ends_with('.')
+        && !value.contains("://");
+    is_path.then(|| value.to_string())
+}
@@ -0,0 +1,502 @@
+//! A client of the Model Context Protocol: ink launches the servers of the `[mcp]` section of
+//! the config as child processes and talks JSON-RPC to them over their stdin and stdout, one
+//! message per line. The tools of the servers are offered to the model next to the local tools
+//! of `tools::TOOLS`, named `<server>__<tool>`.
+
+use log::{error, info};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{
+    collections::HashMap,
+    fmt,
+    process::Stdio,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, Command},
+    sync::oneshot,
+    time,
+};
+
+use crate::{
+    client::ToolSpec,
+    tools::{self, Policy},
+};
+
+/// the version of the protocol ink speaks
+pub const PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// how long a server has to answer anything but a tool call, the tools have the timeout of the
+/// sandbox
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// separates the name of the server from the name of the tool in the names given to the model
+pub const SEPARATOR: &str = "__";
+
+/// A server of the `[mcp]` section of the configuration, keyed by its name.
+///
+/// ```toml
+/// [mcp.git]
+/// command = "uvx"
+/// args = ["mcp-server-git", "--repository", "."]
+/// env = { GIT_AUTHOR_NAME = "ink" }
+/// policy = "allow"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct McpServerConfig {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// added to the environment of ink
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// the policy of every tool of the server, ask by default
+    pub policy: Option<Policy>,
+}
+
+/// A tool of a server, as listed by `tools/list`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpTool {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub input_schema: Value,
+}
+
+/// Something a server can be read, as listed by `resources/list`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct McpResource {
+    pub uri: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// A prompt template of a server, as listed by `prompts/list`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct McpPrompt {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// the requests waiting for their response, by id
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<Value, String>>>>>;
+
+/// A running server and what it offers, the process is killed when the client is dropped.
+pub struct McpClient {
+    pub name: String,
+    pub tools: Vec<McpTool>,
+    pub resources: Vec<McpResource>,
+    pub prompts: Vec<McpPrompt>,
+    stdin: tokio::sync::Mutex<ChildStdin>,
+    pending: Pending,
+    next_id: AtomicU64,
+    child: Mutex<Child>,
+}
+
+impl fmt::Debug for McpClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("McpClient")
+            .field("name", &self.name)
+            .field("tools", &self.tools)
+            .field("resources", &self.resources)
+            .field("prompts", &self.prompts)
+            .finish()
+    }
+}
+
+impl McpClient {
+    /// Launches the server `name`, goes through the handshake and lists what it offers.
+    pub async fn connect(name: &str, config: &McpServerConfig) -> Result<Self, String> {
+        let mut child = Command::new(&config.command)
+            .args(&config.args)
+            .envs(&config.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            // the logs of the server would be drawn over the chat
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Failed to start {}: {}", config.command, e))?;
+
+        let stdin = child.stdin.take().ok_or("The server has no stdin")?;
+        let stdout = child.stdout.take().ok_or("The server has no stdout")?;
+        let pending = Pending::default();
+        tokio::spawn(read_responses(name.to_string(), stdout, pending.clone()));
+
+        let mut client = Self {
+            name: name.to_string(),
+            tools: Vec::new(),
+            resources: Vec::new(),
+            prompts: Vec::new(),
+            stdin: tokio::sync::Mutex::new(stdin),
+            pending,
+            next_id: AtomicU64::new(1),
+            child: Mutex::new(child),
+        };
+
+        let initialized = client
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": { "name": "ink", "version": env!("CARGO_PKG_VERSION") },
+                }),
+            )
+            .await?;
+        client.notify("notifications/initialized").await?;
+
+        let capabilities = &initialized["capabilities"];
+        if capabilities.get("tools").is_some() {
+            client.tools = client.list("tools/list", "tools").await?;
+        }
+        if capabilities.get("resources").is_some() {
+            client.resources = client.list("resources/list", "resources").await?;
+        }
+        if capabilities.get("prompts").is_some() {
+            client.prompts = client.list("prompts/list", "prompts").await?;
+        }
+        Ok(client)
+    }
+
+    /// false once the process exited
+    pub fn is_alive(&self) -> bool {
+        self.child
+            .lock()
+            .map(|mut child| matches!(child.try_wait(), Ok(None)))
+            .unwrap_or(false)
+    }
+
+    /// Calls the tool `name` of the server, returns its text content or, when the server marks
+    /// the result as an error, the text as the reason.
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<String, String> {
+        let result = self
+            .send(
+                "tools/call",
+                json!({ "name": name, "arguments": arguments }),
+            )
+            .await?;
+
+        let text = result["content"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|content| match content["type"].as_str() {
+                Some("text") => content["text"].as_str().unwrap_or_default().to_string(),
+                Some(kind) => format!("[{} content]", kind),
+                None => content.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        match result["isError"].as_bool() {
+            Some(true) => Err(text),
+            _ => Ok(text),
+        }
+    }
+
+    /// every page of the list `method`, the items are under `key`
+    async fn list<T: serde::de::DeserializeOwned>(
+        &self,
+        method: &str,
+        key: &str,
+    ) -> Result<Vec<T>, String> {
+        let mut items = Vec::new();
+        let mut cursor = Value::Null;
+        loop {
+            let params = match &cursor {
+                Value::Null => json!({}),
+                cursor => json!({ "cursor": cursor }),
+            };
+            let mut page = self.request(method, params).await?;
+            let page_items: Vec<T> = serde_json::from_value(page[key].take())
+                .map_err(|e| format!("Invalid answer to {}: {}", method, e))?;
+            items.extend(page_items);
+
+            cursor = page["nextCursor"].take();
+            if cursor.is_null() {
+                return Ok(items);
+            }
+        }
+    }
+
+    /// sends a request and waits `REQUEST_TIMEOUT` for its result
+    async fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        time::timeout(REQUEST_TIMEOUT, self.send(method, params))
+            .await
+            .map_err(|_| format!("{} did not answer {} in time", self.name, method))?
+    }
+
+    /// sends a request and waits for its result, however long it takes
+    async fn send(&self, method: &str, params: Value) -> Result<Value, String> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.insert(id, tx);
+        }
+
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        if let Err(e) = self.write(&message).await {
+            if let Ok(mut pending) = self.pending.lock() {
+                pending.remove(&id);
+            }
+            return Err(e);
+        }
+
+        rx.await
+            .map_err(|_| format!("{} exited before answering {}", self.name, method))?
+    }
+
+    async fn notify(&self, method: &str) -> Result<(), String> {
+        self.write(&json!({ "jsonrpc": "2.0", "method": method }))
+            .await
+    }
+
+    async fn write(&self, message: &Value) -> Result<(), String> {
+        let line = format!("{}\n", message);
+        let mut stdin = self.stdin.lock().await;
+        stdin
+            .write_all(line.as_bytes())
+            .await
+            .and(stdin.flush().await)
+            .map_err(|e| format!("Failed to write to {}: {}", self.name, e))
+    }
+}
+
+/// hands the responses read from `stdout` to the requests waiting for them, until the server
+/// closes it
+async fn read_responses(name: String, stdout: tokio::process::ChildStdout, pending: Pending) {
+    let mut lines = BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let Ok(message) = serde_json::from_str::<Value>(&line) else {
+            error!("{} sent a line that is not JSON: {}", name, line);
+            continue;
+        };
+        // the requests and notifications of the server are not supported, they are ignored
+        if message.get("method").is_some() {
+            continue;
+        }
+        // ink sends numbers, some servers send them back as strings
+        let id = match &message["id"] {
+            Value::Number(id) => id.as_u64(),
+            Value::String(id) => id.parse().ok(),
+            _ => None,
+        };
+
+        let result = match message.get("error") {
+            Some(e) => Err(format!(
+                "{} failed: {}",
+                name,
+                e["message"].as_str().unwrap_or("unknown error")
+            )),
+            None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+        };
+        let waiting = id.and_then(|id| pending.lock().ok()?.remove(&id));
+        match waiting {
+            Some(tx) => {
+                let _ = tx.send(result);
+            }
+            None => error!("{} answered an unknown request {}", name, message["id"]),
+        }
+    }
+
+    info!("{} closed its output", name);
+    // the requests still waiting fail, their senders are dropped
+    if let Ok(mut pending) = pending.lock() {
+        pending.clear();
+    }
+}
+
+/// Where a server is at.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerStatus {
+    Starting,
+    Ready,
+    /// it could not start or it exited, with the reason
+    Failed(String),
+}
+
+/// What the UI shows of a server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerSummary {
+    pub name: String,
+    pub status: ServerStatus,
+    pub tools: usize,
+    pub resources: usize,
+    pub prompts: usize,
+}
+
+impl fmt::Display for ServerSummary {
+    /// ex: "git: 12 tools, 0 resources, 2 prompts"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.status {
+            ServerStatus::Starting => write!(f, "{}: starting", self.name),
+            ServerStatus::Failed(e) => write!(f, "{}: {}", self.name, e),
+            ServerStatus::Ready => write!(
+                f,
+                "{}: {} tools, {} resources, {} prompts",
+                self.name, self.tools, self.resources, self.prompts
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Server {
+    name: String,
+    policy: Policy,
+    status: ServerStatus,
+    client: Option<Arc<McpClient>>,
+}
+
+/// The servers of the configuration, shared by the conversation and the UI. They start in the
+/// background, their tools are offered to the model once they are ready.
+#[derive(Debug, Clone, Default)]
+pub struct Mcp {
+    servers: Arc<Mutex<Vec<Server>>>,
+}
+
+impl Mcp {
+    /// Launches every server of `configs` in the background, in the order of their names. Fails
+    /// on a name containing `SEPARATOR`, its tools could not be told from the ones of another
+    /// server.
+    pub fn start(configs: &HashMap<String, McpServerConfig>) -> Result<Self, String> {
+        let mcp = Self::default();
+        let mut names: Vec<&String> = configs.keys().collect();
+        names.sort();
+
+        if let Some(name) = names.iter().find(|name| name.contains(SEPARATOR)) {
+            return Err(format!(
+                "Invalid MCP server name {}, it cannot contain \"{}\"",
+                name, SEPARATOR
+            ));
+        }
+
+        for name in names {
+            let config = configs[name].clone();
+            if let Ok(mut servers) = mcp.servers.lock() {
+                servers.push(Server {
+                    name: name.clone(),
+                    policy: config.policy.unwrap_or(Policy::Ask),
+                    status: ServerStatus::Starting,
+                    client: None,
+                });
+            }
+
+            let mcp = mcp.clone();
+            let name = name.clone();
+            tokio::spawn(async move {
+                let connected = McpClient::connect(&name, &config).await;
+                if let Err(e) = connected.as_ref() {
+                    error!("Failed to start the MCP server {}: {}", name, e);
+                }
+                mcp.set_client(&name, connected);
+            });
+        }
+
+        Ok(mcp)
+    }
+
+    fn set_client(&self, name: &str, connected: Result<McpClient, String>) {
+        let Ok(mut servers) = self.servers.lock() else {
+            return;
+        };
+        let Some(server) = servers.iter_mut().find(|server| server.name == name) else {
+            return;
+        };
+
+        match connected {
+            Ok(client) => {
+                server.status = ServerStatus::Ready;
+                server.client = Some(Arc::new(client));
+            }
+            Err(e) => server.status = ServerStatus::Failed(e),
+        }
+    }
+
+    /// the state of every server, in the order they were started
+    pub fn summaries(&self) -> Vec<ServerSummary> {
+        let Ok(servers) = self.servers.lock() else {
+            return Vec::new();
+        };
+
+        servers
+            .iter()
+            .map(|server| {
+                let client = server.client.as_ref();
+                let status = match client {
+                    Some(client) if !client.is_alive() => {
+                        ServerStatus::Failed(String::from("exited"))
+                    }
+                    _ => server.status.clone(),
+                };
+                ServerSummary {
+                    name: server.name.clone(),
+                    status,
+                    tools: client.map_or(0, |client| client.tools.len()),
+                    resources: client.map_or(0, |client| client.resources.len()),
+                    prompts: client.map_or(0, |client| client.prompts.len()),
+                }
+            })
+            .collect()
+    }
+
+    /// the tools of the ready servers, described for the model
+    pub fn specs(&self) -> Vec<ToolSpec> {
+        let Ok(servers) = self.servers.lock() else {
+            return Vec::new();
+        };
+
+        servers
+            .iter()
+            .filter_map(|server| server.client.as_ref())
+            .flat_map(|client| {
+                client.tools.iter().map(|tool| {
+                    ToolSpec::function(
+                        format!("{}{}{}", client.name, SEPARATOR, tool.name),
+                        tool.description.clone().unwrap_or_default(),
+                        tool.input_schema.clone(),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// the server of the tool `name`, as given to the model, with the name of the tool on the
+    /// server and the policy of the server
+    pub fn find(&self, name: &str) -> Option<(Arc<McpClient>, String, Policy)> {
+        let (server_name, tool) = name.split_once(SEPARATOR)?;
+        let servers = self.servers.lock().ok()?;
+        let server = servers.iter().find(|server| server.name == server_name)?;
+        let client = server.client.as_ref()?;
+
+        client
+            .tools
+            .iter()
+            .any(|t| t.name == tool)
+            .then(|| (client.clone(), tool.to_string(), server.policy))
+    }
+
+    /// Calls the tool `name` of the server it belongs to, cuts the output like the local
+    /// tools. None when no ready server has the tool.
+    pub async fn call(
+        &self,
+        name: &str,
+        arguments: Value,
+        max_output: usize,
+    ) -> Option<Result<String, String>> {
+        let (client, tool, _) = self.find(name)?;
+        let result = client.call_tool(&tool, arguments).await;
+        Some(result.map(|output| tools::truncate(output, max_output)))
+    }
+}
@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    fs,
+    io::{Error, ErrorKind, Result},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use uuid::Uuid;
+
+use crate::tree::MessageTree;
+
+/// The parameters replies are generated with.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct GenerationSettings {
+    pub model: String,
+    /// None lets ollama use the default temperature of the model
+    pub temperature: Option<f32>,
+    /// sent before the conversation as a system message
+    #[serde(default)]
+    pub system: Option<String>,
+    /// offers the local tools of `tools::TOOLS` to the model
+    #[serde(default)]
+    pub tools: bool,
+}
+
+impl Default for GenerationSettings {
+    fn default() -> Self {
+        Self {
+            model: String::from("deepseek-r1:8b"),
+            temperature: None,
+            system: None,
+            tools: false,
+        }
+    }
+}
+
+/// A conversation that can be saved to disk and resumed later.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Session {
+    pub id: Uuid,
+    pub tree: MessageTree,
+    #[serde(default)]
+    pub settings: GenerationSettings,
+    /// where the session is written to, defaults to a file named after the id inside of
+    /// `Session::dir`
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+/// What the session picker shows about a saved session.
+#[derive(Debug, Clone)]
+pub struct SessionSummary {
+    pub path: PathBuf,
+    /// the first line of the first prompt
+    pub title: String,
+    /// when the session was last saved, "YYYY-MM-DD HH:MM" in UTC
+    pub modified: String,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            ..Default::default()
+        }
+    }
+
+    /// the directory where sessions are stored when no explicit path is given
+    pub fn dir() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("ink")
+            .join("sessions")
+    }
+
+    /// the sessions saved in `Session::dir`, the most recently saved first
+    pub fn list() -> Vec<SessionSummary> {
+        let Ok(entries) = fs::read_dir(Session::dir()) else {
+            return Vec::new();
+        };
+
+        let mut sessions: Vec<(SystemTime, SessionSummary)> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| {
+                let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+                let session = Session::load(&path).ok()?;
+                let title = session
+                    .tree
+                    .active_messages()
+                    .first()
+                    .and_then(|msg| msg.text.lines().next())
+                    .unwrap_or("(empty)")
+                    .chars()
+                    .take(60)
+                    .collect();
+
+                Some((
+                    modified,
+                    SessionSummary {
+                        path,
+                        title,
+                        modified: format_time(modified),
+                    },
+                ))
+            })
+            .collect();
+
+        sessions.sort_by_key(|(modified, _)| Reverse(*modified));
+        sessions.into_iter().map(|(_, summary)| summary).collect()
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.path
+            .clone()
+            .unwrap_or_else(|| Session::dir().join(format!("{}.json", self.id)))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path)?;
+        let mut session: Session =
+            serde_json::from_str(&json).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+        session.path = Some(path.to_path_buf());
+
+        Ok(session)
+    }
+
+    /// Opens the session stored at `path`, or starts a new one that will be saved there.
+    pub fn open(path: &Path) -> Result<Self> {
+        if path.exists() {
+            return Session::load(path);
+        }
+
+        let mut session = Session::new();
+        session.path = Some(path.to_path_buf());
+        Ok(session)
+    }
+
+    /// the displayed branch of the conversation as markdown, one section per message
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::new();
+
+        if let Some(system) = self.settings.system.as_ref() {
+            markdown.push_str(&format!("## System\n\n{}\n\n", system));
+        }
+
+        for msg in self.tree.active_messages() {
+            let title = match msg.model.as_ref() {
+                Some(model) => format!("{} ({})", msg.role, model),
+                None => msg.role.to_string(),
+            };
+            markdown.push_str(&format!("## {}\n\n{}\n\n", title, msg.text.trim_end()));
+        }
+
+        markdown
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if self.tree.is_empty() {
+            return Ok(());
+        }
+
+        let path = self.path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)
+    }
+}
+
+/// formats `time` as "YYYY-MM-DD HH:MM" in UTC
+pub fn format_time(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60
+    )
+}
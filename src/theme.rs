@@ -0,0 +1,241 @@
+use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
+use std::{collections::HashMap, env, str::FromStr};
+
+use crate::conversation::Role;
+
+/// The styles every widget is drawn with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    /// the border of the focused widget
+    pub selected: Style,
+    /// the selected row of the lists, like the palette and the completions
+    pub highlight: Style,
+    /// the border of the other widgets
+    pub border: Style,
+    /// secondary text, like the key of an action or the description of a command
+    pub muted: Style,
+    /// headings and the model in the status line
+    pub accent: Style,
+    pub success: Style,
+    pub error: Style,
+    pub user: Style,
+    pub assistant: Style,
+    pub system: Style,
+    /// the results of the tools called by the model
+    pub tool: Style,
+    /// the attachment chips of a prompt
+    pub chip: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+/// the names of the built-in themes
+pub const THEMES: &[&str] = &["dark", "light", "solarized", "high-contrast"];
+
+fn fg(color: Color) -> Style {
+    Style::default().fg(color)
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            selected: fg(Color::LightGreen),
+            highlight: fg(Color::LightGreen),
+            border: fg(Color::White),
+            muted: fg(Color::DarkGray),
+            accent: fg(Color::LightBlue),
+            success: fg(Color::Green),
+            error: fg(Color::Red),
+            user: fg(Color::LightCyan),
+            assistant: fg(Color::White),
+            system: fg(Color::Yellow),
+            tool: fg(Color::Gray),
+            chip: fg(Color::Black).bg(Color::Cyan),
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            selected: fg(Color::Green),
+            highlight: fg(Color::Green),
+            border: fg(Color::Black),
+            muted: fg(Color::Gray),
+            accent: fg(Color::Blue),
+            success: fg(Color::Green),
+            error: fg(Color::Red),
+            user: fg(Color::Blue),
+            assistant: fg(Color::Black),
+            system: fg(Color::Magenta),
+            tool: fg(Color::DarkGray),
+            chip: fg(Color::White).bg(Color::Blue),
+        }
+    }
+
+    pub fn solarized() -> Self {
+        Self {
+            selected: fg(Color::Rgb(0x85, 0x99, 0x00)),
+            highlight: fg(Color::Rgb(0x85, 0x99, 0x00)),
+            border: fg(Color::Rgb(0x83, 0x94, 0x96)),
+            muted: fg(Color::Rgb(0x58, 0x6e, 0x75)),
+            accent: fg(Color::Rgb(0x26, 0x8b, 0xd2)),
+            success: fg(Color::Rgb(0x85, 0x99, 0x00)),
+            error: fg(Color::Rgb(0xdc, 0x32, 0x2f)),
+            user: fg(Color::Rgb(0x2a, 0xa1, 0x98)),
+            assistant: fg(Color::Rgb(0x83, 0x94, 0x96)),
+            system: fg(Color::Rgb(0xb5, 0x89, 0x00)),
+            tool: fg(Color::Rgb(0x6c, 0x71, 0xc4)),
+            chip: fg(Color::Rgb(0x00, 0x2b, 0x36)).bg(Color::Rgb(0x2a, 0xa1, 0x98)),
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        let bold = Modifier::BOLD;
+        Self {
+            selected: fg(Color::Yellow).add_modifier(bold),
+            highlight: fg(Color::Black).bg(Color::Yellow),
+            border: fg(Color::White),
+            muted: fg(Color::White),
+            accent: fg(Color::LightCyan).add_modifier(bold),
+            success: fg(Color::LightGreen),
+            error: fg(Color::LightRed).add_modifier(bold),
+            user: fg(Color::LightCyan),
+            assistant: fg(Color::White),
+            system: fg(Color::LightMagenta),
+            tool: fg(Color::LightYellow),
+            chip: fg(Color::Black).bg(Color::Yellow),
+        }
+    }
+
+    /// Used when `NO_COLOR` is set, only relies on modifiers so the focus stays visible.
+    pub fn no_color() -> Self {
+        let plain = Style::default();
+        Self {
+            selected: plain.add_modifier(Modifier::BOLD),
+            highlight: plain.add_modifier(Modifier::REVERSED),
+            border: plain,
+            muted: plain.add_modifier(Modifier::DIM),
+            accent: plain.add_modifier(Modifier::BOLD),
+            success: plain,
+            error: plain.add_modifier(Modifier::BOLD),
+            user: plain,
+            assistant: plain,
+            system: plain.add_modifier(Modifier::ITALIC),
+            tool: plain.add_modifier(Modifier::DIM),
+            chip: plain.add_modifier(Modifier::REVERSED),
+        }
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "solarized" => Some(Self::solarized()),
+            "high-contrast" => Some(Self::high_contrast()),
+            _ => None,
+        }
+    }
+
+    /// Picks the theme named `name` among the built-in themes and the ones of the
+    /// configuration, `NO_COLOR` (https://no-color.org) wins over both.
+    pub fn load(name: Option<&str>, themes: &HashMap<String, ThemeConfig>) -> Result<Self, String> {
+        if env::var("NO_COLOR").is_ok_and(|value| !value.is_empty()) {
+            return Ok(Self::no_color());
+        }
+
+        let name = name.unwrap_or("dark");
+        if let Some(theme) = themes.get(name) {
+            return theme.build().map_err(|e| format!("Theme {}: {}", name, e));
+        }
+        Self::builtin(name).ok_or_else(|| {
+            format!(
+                "Unknown theme \"{}\", expected one of {} or a [themes.{}] section",
+                name,
+                THEMES.join(", "),
+                name
+            )
+        })
+    }
+
+    /// the style of the messages of `role`
+    pub fn role(&self, role: &Role) -> Style {
+        match role {
+            Role::User => self.user,
+            Role::Assistant => self.assistant,
+            Role::System => self.system,
+            Role::Error => self.error,
+            Role::Tool => self.tool,
+        }
+    }
+}
+
+/// A theme of the configuration, the colors it leaves out come from `base`. Colors are names
+/// like "light-green", "#rrggbb" values or indexes of the 256 color palette.
+///
+/// ```toml
+/// theme = "mine"
+///
+/// [themes.mine]
+/// base = "solarized"
+/// selected = "#d33682"
+/// user = "light-cyan"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    /// one of `THEMES`, "dark" by default
+    pub base: Option<String>,
+    pub selected: Option<String>,
+    pub highlight: Option<String>,
+    pub border: Option<String>,
+    pub muted: Option<String>,
+    pub accent: Option<String>,
+    pub success: Option<String>,
+    pub error: Option<String>,
+    pub user: Option<String>,
+    pub assistant: Option<String>,
+    pub system: Option<String>,
+    pub tool: Option<String>,
+    /// the background of the attachment chips
+    pub chip: Option<String>,
+}
+
+impl ThemeConfig {
+    pub fn build(&self) -> Result<Theme, String> {
+        let base = self.base.as_deref().unwrap_or("dark");
+        let mut theme =
+            Theme::builtin(base).ok_or_else(|| format!("Unknown base theme \"{}\"", base))?;
+
+        let foregrounds = [
+            (&self.selected, &mut theme.selected),
+            (&self.highlight, &mut theme.highlight),
+            (&self.border, &mut theme.border),
+            (&self.muted, &mut theme.muted),
+            (&self.accent, &mut theme.accent),
+            (&self.success, &mut theme.success),
+            (&self.error, &mut theme.error),
+            (&self.user, &mut theme.user),
+            (&self.assistant, &mut theme.assistant),
+            (&self.system, &mut theme.system),
+            (&self.tool, &mut theme.tool),
+        ];
+        for (color, style) in foregrounds {
+            if let Some(color) = color {
+                *style = style.fg(parse_color(color)?);
+            }
+        }
+        if let Some(color) = &self.chip {
+            theme.chip = theme.chip.bg(parse_color(color)?);
+        }
+
+        Ok(theme)
+    }
+}
+
+fn parse_color(color: &str) -> Result<Color, String> {
+    Color::from_str(color).map_err(|_| format!("Invalid color \"{}\"", color))
+}
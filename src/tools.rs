@@ -0,0 +1,603 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    client::{ToolCall, ToolSpec},
+    mcp,
+};
+
+/// What a tool sends back to the model, errors are sent too so the model can try again.
+pub type ToolResult = Result<String, String>;
+
+/// Whether a tool runs when the model calls it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Policy {
+    /// runs without asking
+    Allow,
+    /// waits for the user to confirm the call
+    Ask,
+    /// never runs, the model is told the tool is not allowed
+    Deny,
+}
+
+/// A local tool the model can call when `GenerationSettings::tools` is set.
+#[derive(Debug)]
+pub struct Tool {
+    pub name: &'static str,
+    /// tells the model when to call the tool
+    pub description: &'static str,
+    /// the JSON schema of the arguments
+    pub parameters: &'static str,
+    /// used unless the configuration sets another policy for the tool
+    pub policy: Policy,
+    pub run: fn(&Value, &Sandbox) -> ToolResult,
+}
+
+impl Tool {
+    pub fn spec(&self) -> ToolSpec {
+        let parameters = serde_json::from_str(self.parameters)
+            .unwrap_or_else(|e| panic!("Invalid parameters of the tool {}: {}", self.name, e));
+        ToolSpec::function(self.name, self.description, parameters)
+    }
+}
+
+/// the most lines `grep` returns, the model can narrow the search down
+const MAX_MATCHES: usize = 100;
+
+/// Every tool offered to the model, adding an entry here is enough to make a new tool available.
+pub const TOOLS: &[Tool] = &[
+    Tool {
+        name: "read_file",
+        description: "Read a text file and return its content.",
+        parameters: r#"{
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "the path of the file" }
+            },
+            "required": ["path"]
+        }"#,
+        policy: Policy::Allow,
+        run: |args, sandbox| {
+            let path = string(args, "path")?;
+            read_file(&sandbox.resolve(path)?, sandbox.max_output)
+                .map_err(|e| format!("Failed to read {}: {}", path, e))
+        },
+    },
+    Tool {
+        name: "list_directory",
+        description: "List the files and directories of a directory, directories end with '/'.",
+        parameters: r#"{
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "the path of the directory, the current directory by default"
+                }
+            }
+        }"#,
+        policy: Policy::Allow,
+        run: |args, sandbox| {
+            let path = optional_string(args, "path")?.unwrap_or(".");
+            list_directory(&sandbox.resolve(path)?)
+                .map(|output| truncate(output, sandbox.max_output))
+                .map_err(|e| format!("Failed to list {}: {}", path, e))
+        },
+    },
+    Tool {
+        name: "grep",
+        description: "Search the files of a directory and its subdirectories for lines \
+                      containing a text, returns the matching lines as `path:line: text`.",
+        parameters: r#"{
+            "type": "object",
+            "properties": {
+                "pattern": { "type": "string", "description": "the text to look for" },
+                "path": {
+                    "type": "string",
+                    "description": "the file or directory to search, the current directory by default"
+                }
+            },
+            "required": ["pattern"]
+        }"#,
+        policy: Policy::Allow,
+        run: |args, sandbox| {
+            let pattern = string(args, "pattern")?;
+            let path = optional_string(args, "path")?.unwrap_or(".");
+            let output = grep(pattern, &sandbox.resolve(path)?, &sandbox.root);
+            Ok(truncate(output, sandbox.max_output))
+        },
+    },
+    Tool {
+        name: "run_shell",
+        description: "Run a shell command and return what it printed and its exit code.",
+        parameters: r#"{
+            "type": "object",
+            "properties": {
+                "command": { "type": "string", "description": "the command line" }
+            },
+            "required": ["command"]
+        }"#,
+        policy: Policy::Ask,
+        run: |args, sandbox| {
+            run_shell(string(args, "command")?, sandbox)
+                .map(|output| truncate(output, sandbox.max_output))
+        },
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Tool> {
+    TOOLS.iter().find(|tool| tool.name == name)
+}
+
+/// the descriptions of every tool, sent with the requests
+pub fn specs() -> Vec<ToolSpec> {
+    TOOLS.iter().map(Tool::spec).collect()
+}
+
+/// the longest output sent back to the model by default, in bytes
+pub const DEFAULT_MAX_OUTPUT: usize = 16 * 1024;
+
+/// how long a tool runs by default before it is stopped, in seconds
+pub const DEFAULT_TIMEOUT: u64 = 30;
+
+/// The limits the tools run within: which files they see, how long they run and how much they
+/// send back, with the policy of each tool.
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    /// the file tools only reach the files under this directory, the commands start there;
+    /// kept canonical so the resolved paths can be compared with it
+    pub root: PathBuf,
+    pub timeout: Duration,
+    /// the output is cut past this many bytes
+    pub max_output: usize,
+    /// the policies set by the user, by tool name
+    pub policies: HashMap<String, Policy>,
+}
+
+impl Default for Sandbox {
+    /// the current directory, with the default limits and policies
+    fn default() -> Self {
+        let root = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        Self {
+            root: root.canonicalize().unwrap_or(root),
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT),
+            max_output: DEFAULT_MAX_OUTPUT,
+            policies: HashMap::new(),
+        }
+    }
+}
+
+impl Sandbox {
+    /// the policy of the tool `name`, calls of unknown tools fail without running anything
+    pub fn policy(&self, name: &str) -> Policy {
+        self.policies
+            .get(name)
+            .copied()
+            .or(find(name).map(|tool| tool.policy))
+            .unwrap_or(Policy::Allow)
+    }
+
+    /// Resolves `path`, relative to the root, to an existing path under the root. The links
+    /// are followed, a link pointing out of the root is refused like `..`.
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        let resolved = self
+            .root
+            .join(path)
+            .canonicalize()
+            .map_err(|e| format!("Failed to open {}: {}", path, e))?;
+
+        if !resolved.starts_with(&self.root) {
+            return Err(format!(
+                "{} is outside of the working directory {}",
+                path,
+                self.root.display()
+            ));
+        }
+        Ok(resolved)
+    }
+
+    /// Runs `call`, each tool cuts its output to `max_output` bytes. Returns the output of the
+    /// tool or the reason it failed. Only the commands stop themselves after `timeout`, the caller
+    /// stops waiting for the other tools.
+    pub fn run(&self, call: &ToolCall) -> ToolResult {
+        let tool = find(&call.function.name)
+            .ok_or_else(|| format!("there is no tool named {}", call.function.name))?;
+
+        (tool.run)(&arguments(call), self)
+    }
+}
+
+/// The `[tools]` section of the configuration.
+///
+/// ```toml
+/// [tools]
+/// root = "~/projects/ink"
+/// timeout = 10
+/// max_output = 4096
+///
+/// [tools.policy]
+/// run_shell = "deny"
+/// grep = "ask"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ToolsConfig {
+    /// the working directory of the tools, the directory ink is started from by default
+    pub root: Option<PathBuf>,
+    /// in seconds
+    pub timeout: u64,
+    /// in bytes
+    pub max_output: usize,
+    /// allow, ask or deny, by tool name, `<server>__<tool>` for the tools of the MCP servers
+    pub policy: HashMap<String, Policy>,
+}
+
+impl Default for ToolsConfig {
+    fn default() -> Self {
+        Self {
+            root: None,
+            timeout: DEFAULT_TIMEOUT,
+            max_output: DEFAULT_MAX_OUTPUT,
+            policy: HashMap::new(),
+        }
+    }
+}
+
+impl ToolsConfig {
+    /// The sandbox described by the section, fails on a missing root or an unknown local tool.
+    /// The tools of the MCP servers are only listed once the servers started, their names are
+    /// taken as they are.
+    pub fn sandbox(&self) -> Result<Sandbox, String> {
+        if let Some(name) = self
+            .policy
+            .keys()
+            .find(|name| find(name).is_none() && !name.contains(mcp::SEPARATOR))
+        {
+            return Err(format!("Unknown tool {} in [tools.policy]", name));
+        }
+
+        let mut sandbox = Sandbox {
+            timeout: Duration::from_secs(self.timeout),
+            max_output: self.max_output,
+            policies: self.policy.clone(),
+            ..Sandbox::default()
+        };
+        if let Some(root) = self.root.as_ref() {
+            let root = match root.strip_prefix("~") {
+                Ok(rest) => dirs::home_dir().unwrap_or_default().join(rest),
+                Err(_) => root.clone(),
+            };
+            sandbox.root = root
+                .canonicalize()
+                .map_err(|e| format!("Invalid tools root {}: {}", root.display(), e))?;
+        }
+        Ok(sandbox)
+    }
+}
+
+/// How a call came to run, or not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    /// the policy of the tool is allow
+    Allowed,
+    /// the user confirmed the call
+    Confirmed,
+    /// the user refused the call
+    Refused,
+    /// the policy of the tool is deny
+    Denied,
+}
+
+/// A line of the audit log, written for every call the model makes.
+#[derive(Debug, Serialize)]
+pub struct AuditEntry<'a> {
+    /// in seconds since the unix epoch
+    pub time: u64,
+    pub tool: &'a str,
+    pub arguments: &'a Value,
+    pub decision: Decision,
+    /// None when the call did not run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u128>,
+    /// the size of what was sent back to the model
+    pub output_bytes: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<&'a str>,
+}
+
+impl<'a> AuditEntry<'a> {
+    pub fn new(call: &'a ToolCall, decision: Decision, result: &'a ToolResult) -> Self {
+        let (output_bytes, error) = match result {
+            Ok(output) => (output.len(), None),
+            Err(e) => (e.len(), Some(e.as_str())),
+        };
+        Self {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            tool: &call.function.name,
+            arguments: &call.function.arguments,
+            decision,
+            duration_ms: None,
+            output_bytes,
+            error,
+        }
+    }
+
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration_ms = Some(duration.as_millis());
+        self
+    }
+
+    /// appends the entry to the log at `path`, one JSON object per line
+    pub fn append(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        let line = serde_json::to_string(self).map_err(std::io::Error::other)?;
+        writeln!(file, "{}", line)
+    }
+}
+
+/// the audit log of the session saved at `session`, next to it
+pub fn audit_path(session: &Path) -> PathBuf {
+    session.with_extension("audit.jsonl")
+}
+
+/// the arguments of `call`, some models send them as a string of JSON
+pub fn arguments(call: &ToolCall) -> Value {
+    match &call.function.arguments {
+        Value::String(text) => serde_json::from_str(text).unwrap_or(Value::Null),
+        arguments => arguments.clone(),
+    }
+}
+
+fn string<'a>(args: &'a Value, name: &str) -> Result<&'a str, String> {
+    optional_string(args, name)?.ok_or_else(|| format!("Missing argument \"{}\"", name))
+}
+
+fn optional_string<'a>(args: &'a Value, name: &str) -> Result<Option<&'a str>, String> {
+    match args.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value)),
+        Some(_) => Err(format!("The argument \"{}\" must be a string", name)),
+    }
+}
+
+/// cuts `output` to `max` bytes, on a character boundary, and tells how much was left out
+pub fn truncate(output: String, max: usize) -> String {
+    let len = output.len();
+    cut(output, max, Some(len))
+}
+
+/// like `truncate`, `len` is the length of the whole output when it is known, `output` can be
+/// only its beginning
+fn cut(mut output: String, max: usize, len: Option<usize>) -> String {
+    if output.len() <= max && len == Some(output.len()) {
+        return output;
+    }
+
+    let mut end = max.min(output.len());
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    output.truncate(end);
+    match len {
+        Some(len) => output.push_str(&format!("\n... output truncated, {} more bytes", len - end)),
+        None => output.push_str("\n... output truncated"),
+    }
+    output
+}
+
+/// The text of the file at `path`, cut like `truncate` past `max` bytes. No more than `max + 1`
+/// bytes are read, a huge file or a device like `/dev/zero` would fill the memory otherwise.
+fn read_file(path: &Path, max: usize) -> io::Result<String> {
+    let file = File::open(path)?;
+    // the size of a device or a pipe is reported as 0
+    let size = file.metadata()?.len() as usize;
+    let mut bytes = Vec::new();
+    file.take(max as u64 + 1).read_to_end(&mut bytes)?;
+
+    let read = bytes.len();
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "stream did not contain valid UTF-8",
+        )
+    };
+    if read <= max {
+        return String::from_utf8(bytes).map_err(|_| invalid());
+    }
+
+    let text = match String::from_utf8(bytes) {
+        Ok(text) => text,
+        // the last character can be cut by the limit
+        Err(e) if e.utf8_error().error_len().is_none() => {
+            let valid = e.utf8_error().valid_up_to();
+            let mut bytes = e.into_bytes();
+            bytes.truncate(valid);
+            String::from_utf8(bytes).unwrap_or_default()
+        }
+        Err(_) => return Err(invalid()),
+    };
+    Ok(cut(text, max, (size >= read).then_some(size)))
+}
+
+fn list_directory(path: &Path) -> std::io::Result<String> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let mut name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type()?.is_dir() {
+            name.push('/');
+        }
+        names.push(name);
+    }
+
+    if names.is_empty() {
+        return Ok(String::from("The directory is empty"));
+    }
+    names.sort();
+    Ok(names.join("\n"))
+}
+
+/// the lines of the files under `path` that contain `pattern`, hidden files are skipped and the
+/// paths are shown relative to `root`
+fn grep(pattern: &str, path: &Path, root: &Path) -> String {
+    let mut files = Vec::new();
+    collect_files(path, &mut files);
+    files.sort();
+
+    let mut matches = Vec::new();
+    let mut left_out = 0;
+    for file in files {
+        // binary and unreadable files are skipped
+        let Ok(text) = fs::read_to_string(&file) else {
+            continue;
+        };
+        let name = file.strip_prefix(root).unwrap_or(&file);
+        for (n, line) in text.lines().enumerate() {
+            if !line.contains(pattern) {
+                continue;
+            }
+            if matches.len() < MAX_MATCHES {
+                matches.push(format!("{}:{}: {}", name.display(), n + 1, line.trim_end()));
+            } else {
+                left_out += 1;
+            }
+        }
+    }
+
+    if matches.is_empty() {
+        return format!("No line contains \"{}\"", pattern);
+    }
+    if left_out > 0 {
+        matches.push(format!("... and {} more matches", left_out));
+    }
+    matches.join("\n")
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) {
+    if path.is_file() {
+        files.push(path.to_path_buf());
+        return;
+    }
+
+    let Ok(entries) = fs::read_dir(path) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        // the links could lead out of the root
+        if entry.file_type().is_ok_and(|kind| kind.is_symlink()) {
+            continue;
+        }
+        collect_files(&entry.path(), files);
+    }
+}
+
+/// Runs `command` in the root, killed when it runs past the timeout. The command is not
+/// confined to the root, the policy of the tool is what protects the rest of the system.
+fn run_shell(command: &str, sandbox: &Sandbox) -> ToolResult {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    };
+    let mut child = shell
+        .current_dir(&sandbox.root)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", command, e))?;
+
+    // both pipes are read at once, a command filling one of them would block otherwise
+    let (tx, rx) = mpsc::channel();
+    read_in_background(child.stdout.take(), tx.clone());
+    read_in_background(child.stderr.take(), tx);
+
+    let deadline = Instant::now() + sandbox.timeout;
+    let mut output = Vec::new();
+    let mut timed_out = false;
+    loop {
+        match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            // past the cap the output is only drained, `truncate` tells how much was left out
+            Ok(chunk) if output.len() <= sandbox.max_output => output.extend(chunk),
+            Ok(_) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {
+                timed_out = true;
+                break;
+            }
+        }
+    }
+
+    // the pipes close before the command exits when it closes them itself
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) if !timed_out => break Some(status),
+            Ok(None) if !timed_out && Instant::now() < deadline => {
+                thread::sleep(Duration::from_millis(10))
+            }
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                break None;
+            }
+        }
+    };
+
+    let mut result = String::from_utf8_lossy(&output).to_string();
+    match status {
+        None => result.push_str(&format!(
+            "\nThe command was stopped after {}s",
+            sandbox.timeout.as_secs_f64()
+        )),
+        Some(status) if !status.success() => {
+            let code = status
+                .code()
+                .map_or(String::from("none"), |code| code.to_string());
+            result.push_str(&format!("\nexit code: {}", code));
+        }
+        Some(_) => {}
+    }
+    if result.trim().is_empty() {
+        result = String::from("The command printed nothing");
+    }
+    Ok(result)
+}
+
+/// sends what `pipe` outputs to `tx` until it closes
+fn read_in_background(pipe: Option<impl Read + Send + 'static>, tx: mpsc::Sender<Vec<u8>>) {
+    let Some(mut pipe) = pipe else {
+        return;
+    };
+    thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        while let Ok(n) = pipe.read(&mut buffer) {
+            if n == 0 || tx.send(buffer[..n].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+}
@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::conversation::ChatMessage;
+
+/// Every message of a conversation, including the alternatives created when a reply is
+/// regenerated or a prompt is edited. Messages are keyed by their id and linked to their
+/// parent, only one branch (the active path) is displayed at a time. The paths stop at an id
+/// without a message, so a damaged session file is shown up to the damage.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct MessageTree {
+    nodes: HashMap<Uuid, ChatMessage>,
+    roots: Vec<Uuid>,
+    active_root: usize,
+}
+
+impl MessageTree {
+    pub fn get(&self, id: &Uuid) -> Option<&ChatMessage> {
+        self.nodes.get(id)
+    }
+
+    pub fn get_mut(&mut self, id: &Uuid) -> Option<&mut ChatMessage> {
+        self.nodes.get_mut(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// iterates over every node, including the ones outside of the active path
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut ChatMessage> {
+        self.nodes.values_mut()
+    }
+
+    /// the ids of the messages currently displayed, from the first message to the last one
+    pub fn active_path(&self) -> Vec<Uuid> {
+        let mut path = Vec::new();
+        let mut current = self.roots.get(self.active_root).copied();
+
+        // a cycle in a damaged file would never end otherwise
+        while let Some(node) = current.and_then(|id| self.nodes.get(&id))
+            && path.len() < self.nodes.len()
+        {
+            path.push(node.id);
+            current = node.children.get(node.active_child).copied();
+        }
+
+        path
+    }
+
+    /// the messages of the active path, in order
+    pub fn active_messages(&self) -> Vec<&ChatMessage> {
+        self.active_path()
+            .iter()
+            .filter_map(|id| self.nodes.get(id))
+            .collect()
+    }
+
+    /// the ids from the first message to `id`, following the parents whatever the active path
+    pub fn path_to(&self, id: &Uuid) -> Vec<Uuid> {
+        let mut path = Vec::new();
+        let mut current = self.nodes.get(id);
+
+        while let Some(node) = current
+            && path.len() < self.nodes.len()
+        {
+            path.push(node.id);
+            current = node.parent.and_then(|parent| self.nodes.get(&parent));
+        }
+
+        path.reverse();
+        path
+    }
+
+    pub fn last(&self) -> Option<Uuid> {
+        self.active_path().last().copied()
+    }
+
+    /// Appends `msg` as a child of `parent`, or as a new root when `parent` is None, and makes
+    /// it the active branch. If the parent already has children, the new message becomes an
+    /// alternative to them.
+    pub fn push(&mut self, parent: Option<Uuid>, mut msg: ChatMessage) -> Uuid {
+        let id = msg.id;
+        msg.parent = parent;
+
+        match parent.and_then(|p| self.nodes.get_mut(&p)) {
+            Some(node) => {
+                msg.index = node.index + 1;
+                node.children.push(id);
+                node.active_child = node.children.len() - 1;
+            }
+            None => {
+                msg.index = 0;
+                self.roots.push(id);
+                self.active_root = self.roots.len() - 1;
+            }
+        }
+
+        self.nodes.insert(id, msg);
+        id
+    }
+
+    fn siblings_of(&self, id: &Uuid) -> &[Uuid] {
+        match self.nodes.get(id).and_then(|n| n.parent) {
+            Some(parent) => self
+                .nodes
+                .get(&parent)
+                .map_or(&[], |node| node.children.as_slice()),
+            None => &self.roots,
+        }
+    }
+
+    /// returns the position of the message among its siblings and how many siblings there are
+    pub fn siblings(&self, id: &Uuid) -> (usize, usize) {
+        let siblings = self.siblings_of(id);
+        let position = siblings.iter().position(|s| s == id).unwrap_or(0);
+        (position, siblings.len())
+    }
+
+    /// Makes the next (or previous) alternative of the message the active one and returns its
+    /// id. Does nothing when there is no alternative in that direction.
+    pub fn select_sibling(&mut self, id: &Uuid, forward: bool) -> Option<Uuid> {
+        let (position, count) = self.siblings(id);
+
+        let new_position = if forward {
+            position + 1
+        } else {
+            position.checked_sub(1)?
+        };
+
+        if new_position >= count {
+            return None;
+        }
+
+        let new_id = self.siblings_of(id)[new_position];
+        match self.nodes.get(id).and_then(|n| n.parent) {
+            Some(parent) => self.nodes.get_mut(&parent)?.active_child = new_position,
+            None => self.active_root = new_position,
+        }
+
+        Some(new_id)
+    }
+}
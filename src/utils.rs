@@ -1,18 +1,29 @@
 use crate::widgets::message::OFFSET;
+use ratatui::layout::{Constraint, Flex, Layout, Rect};
 
-pub fn get_height(text: &String) -> u16 {
+pub fn get_height(text: &str) -> u16 {
     let count = text.split('\n').count();
     (count + OFFSET).max(3) as u16
 }
 
-pub fn get_longest_string(text: &String) -> usize {
+pub fn get_longest_string(text: &str) -> usize {
     let mut longest: usize = 0;
 
-    text.clone().split('\n').into_iter().for_each(|item| {
-        if item.len() > longest as usize {
-            longest = item.len() as usize;
+    text.split('\n').for_each(|item| {
+        if item.len() > longest {
+            longest = item.len();
         }
     });
 
-    return longest;
+    longest
+}
+
+/// a rectangle centered in `area`, taking the given percentage of its width and height
+pub fn centered_rect(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let vertical = Layout::vertical([Constraint::Percentage(percent_y)])
+        .flex(Flex::Center)
+        .split(area);
+    Layout::horizontal([Constraint::Percentage(percent_x)])
+        .flex(Flex::Center)
+        .split(vertical[0])[0]
 }
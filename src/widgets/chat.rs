@@ -1,24 +1,65 @@
-use crate::widgets::{
-    message::{Message, OFFSET, Role},
-    textarea::TextArea,
+use crate::{
+    actions::Action,
+    client::Provider,
+    commands::{self, Input},
+    conversation::{ChatMessage, Conversation, ConversationEvent, Role},
+    history::History,
+    keymap::{Context, Keymap},
+    mcp::Mcp,
+    session::{GenerationSettings, Session},
+    theme::Theme,
+    tools::Sandbox,
+    utils::get_longest_string,
+    widgets::{
+        history_search::HistorySearch,
+        message::{Message, MessageState, OFFSET},
+        textarea::TextArea,
+        vim::Mode,
+    },
 };
-use futures_util::stream::StreamExt;
-use ratatui::crossterm::event::{Event, KeyCode, KeyModifiers};
+use log::error;
+use ratatui::crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 use ratatui::{
     buffer::Buffer,
     layout::{Constraint, Layout, Rect},
-    widgets::{Block, Scrollbar, ScrollbarOrientation, ScrollbarState, StatefulWidget, Widget},
+    widgets::{
+        Block, Clear, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, StatefulWidget,
+        Widget,
+    },
 };
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use std::{io::Result, sync::mpsc};
-use tokio::task;
+use std::{collections::HashMap, env, io::Result, sync::Arc};
+use uuid::Uuid;
 
+/// A `Conversation` and the textarea the prompts are typed in, drawn by `ChatView`. Configured
+/// with the `with_` methods, the app reads what happened with `take_events`.
 #[derive(Debug)]
-pub struct Chat<'a> {
+pub struct Chat {
+    /// position of the selected message in the active path
     pub selected_message_id: usize,
-    pub messages: Vec<Message>,
-    pub textarea: TextArea<'a>,
+    /// the messages and the replies being generated
+    pub conversation: Conversation,
+    /// how each message is displayed, by message id
+    pub views: HashMap<Uuid, MessageState>,
+    pub textarea: TextArea<'static>,
+    /// the keys of the input and of the history search
+    pub keymap: Keymap,
+    /// the user message being edited, submitting the textarea creates an alternative to it
+    pub editing: Option<Uuid>,
+    /// the prompts submitted so far, recalled with Up/Down
+    pub history: History,
+    /// set while searching the history with Ctrl+R
+    pub search: Option<HistorySearch>,
+    /// a short message for the status line, taken by the app once displayed
+    pub notification: Option<String>,
+    /// renders the messages as markdown instead of plain text
+    pub markdown: bool,
+    /// drops the borders of the messages to fit more text
+    pub compact: bool,
+    /// right aligns the prompts in bubbles as wide as their text
+    pub bubbles: bool,
+    /// shown in the header of the prompts
+    pub user_name: String,
+    pub theme: Theme,
     /// the total height of the chat screen including the entire chat history
     pub height: usize,
     /// used to render the scrollbar, represents where
@@ -27,83 +68,311 @@ pub struct Chat<'a> {
     /// used to render the scrollbar, it represents
     /// how much space the scrollbar will have for scrolling
     pub scroll_area: usize,
-    rx: mpsc::Receiver<Chunk>,
-    tx: mpsc::Sender<Chunk>,
+    /// keeps the bottom of the chat in view as replies stream in, scrolling up disengages it
+    pub follow: bool,
+    /// set when the chat grew below the view while not following
+    pub unseen: bool,
 }
 
-#[derive(Deserialize, Serialize, Debug, Default)]
-struct Chunk {
-    model: String,
-    created_at: String,
-    done: bool,
-    message: Msg,
+/// The name of the user from the environment, empty when unknown.
+pub fn default_user_name() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_default()
 }
 
-#[derive(Deserialize, Serialize, Debug, Default)]
-struct Msg {
-    role: String,
-    content: String,
-}
-
-#[derive(Deserialize, Serialize, Debug)]
-struct OllamaReqBody {
-    model: String,
-    messages: Vec<Msg>,
-}
+pub const MARGIN: i32 = 1;
 
-impl Default for OllamaReqBody {
-    fn default() -> Self {
-        Self {
-            model: String::from("deepseek-r1:8b"),
-            messages: Vec::new(),
-        }
+/// the horizontal position and the width of `msg` in `area`, prompts are right aligned in
+/// bubbles as wide as their text when `bubbles` is set
+fn horizontal_bounds(
+    msg: &ChatMessage,
+    state: &MessageState,
+    area: Rect,
+    bubbles: bool,
+) -> (u16, u16) {
+    if !bubbles || msg.role != Role::User {
+        return (area.x, area.width);
     }
-}
 
-pub const MARGIN: i32 = 1;
+    let borders = if state.compact { 0 } else { 2 };
+    let text = get_longest_string(&msg.text) + borders + 2;
+    let header = state.header(msg).chars().count() + borders + 2;
+    let max = (area.width as usize * 3 / 4).max(1);
+    let width = text.max(header).min(max) as u16;
+    (area.x + area.width - width, width)
+}
 
-impl<'a> Chat<'a> {
+impl Chat {
     pub fn new(input: Vec<(String, Role)>) -> Self {
-        let mut messages: Vec<Message> = input
-            .into_iter()
-            .enumerate()
-            .map(|(i, item)| Message::new(i, item.0, false, item.1))
-            .collect();
+        let mut session = Session::new();
 
-        if messages.len() > 0 {
-            messages[0].is_selected = true;
+        let mut parent = None;
+        for (text, role) in input {
+            parent = Some(session.tree.push(parent, ChatMessage::new(role, text)));
         }
 
-        let textarea = TextArea::default();
-        let (tx, rx) = mpsc::channel::<Chunk>();
+        Chat::from_session(session)
+    }
 
-        Self {
-            rx,
-            tx,
-            messages,
-            textarea,
+    pub fn from_session(session: Session) -> Self {
+        let mut chat = Self {
+            conversation: Conversation::new(session),
+            views: HashMap::new(),
+            textarea: TextArea::default(),
+            keymap: Keymap::default(),
+            editing: None,
+            history: History::default(),
+            search: None,
+            notification: None,
+            markdown: false,
+            compact: false,
+            bubbles: false,
+            user_name: default_user_name(),
+            theme: Theme::default(),
             height: 0,
             scroll_area: 0,
             scroll_state: 0,
+            follow: true,
+            unseen: false,
             selected_message_id: 0,
+        };
+        chat.set_selected(0, true);
+        chat
+    }
+
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    pub fn with_keymap(mut self, keymap: Keymap) -> Self {
+        self.keymap = keymap;
+        self
+    }
+
+    /// asks `provider` for the replies instead of the ollama server at `default_host`
+    pub fn with_provider(mut self, provider: impl Provider + 'static) -> Self {
+        self.conversation.provider = Arc::new(provider);
+        self
+    }
+
+    /// the limits and the policies of the tools called by the model
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.conversation.sandbox = sandbox;
+        self
+    }
+
+    /// offers the tools of the MCP servers of `mcp` to the model
+    pub fn with_mcp(mut self, mcp: Mcp) -> Self {
+        self.conversation.mcp = mcp;
+        self
+    }
+
+    /// recalls the prompts of `history`, like `History::open()` for the history file of the
+    /// user; the prompts are only kept in memory otherwise
+    pub fn with_history(mut self, history: History) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// edits the prompt with vim keybindings
+    pub fn with_vim(mut self, vim: bool) -> Self {
+        self.textarea = TextArea::default().with_vim(vim);
+        self
+    }
+
+    pub fn with_markdown(mut self, markdown: bool) -> Self {
+        self.markdown = markdown;
+        self
+    }
+
+    pub fn with_compact(mut self, compact: bool) -> Self {
+        self.compact = compact;
+        self
+    }
+
+    pub fn with_bubbles(mut self, bubbles: bool) -> Self {
+        self.bubbles = bubbles;
+        self
+    }
+
+    pub fn with_user_name(mut self, user_name: impl Into<String>) -> Self {
+        self.user_name = user_name.into();
+        self
+    }
+
+    /// replaces the displayed conversation, replies still streaming to the previous one are
+    /// dropped
+    pub fn set_session(&mut self, session: Session) {
+        self.conversation.set_session(session);
+        self.views.clear();
+        self.editing = None;
+        self.selected_message_id = 0;
+        self.scroll_state = 0;
+        self.follow = true;
+        self.unseen = false;
+        self.set_selected(0, true);
+    }
+
+    /// Runs the slash command typed in the textarea, or sends it as a prompt. An invalid command
+    /// is left in the textarea so it can be fixed.
+    pub fn submit(&mut self) {
+        let text = self.textarea.text().trim().to_string();
+        if text.is_empty() {
+            return;
+        }
+
+        let input = match commands::parse(&text) {
+            Ok(input) => input,
+            Err(e) => {
+                self.notification = Some(e);
+                return;
+            }
+        };
+
+        self.textarea.clear();
+        if let Err(e) = self.history.push(&text) {
+            error!("Failed to save the prompt history: {}", e);
+        }
+
+        match input {
+            Input::Command(command, args) => {
+                self.editing = None;
+                self.notification = Some((command.run)(self, args).unwrap_or_else(|e| e));
+            }
+            Input::Prompt(prompt) => {
+                self.scroll_to_bottom();
+                self.conversation
+                    .submit(prompt.to_string(), self.editing.take());
+            }
+        }
+    }
+
+    /// runs the slash command `name` as if it was typed in the textarea
+    pub fn run_command(&mut self, name: &str, args: &str) {
+        let Some(command) = commands::find(name) else {
+            return;
+        };
+        self.notification = Some((command.run)(self, args).unwrap_or_else(|e| e));
+    }
+
+    /// starts searching the prompt history, like Ctrl+R in a shell
+    pub fn open_search(&mut self) {
+        let mut search = HistorySearch::default();
+        search.update(&self.history);
+        self.search = Some(search);
+    }
+
+    /// whether Esc is used by the input, to close the history search or leave a vim mode
+    pub fn captures_esc(&self) -> bool {
+        self.search.is_some() || self.textarea.captures_esc()
+    }
+
+    fn handle_search_events(&mut self, key: KeyEvent) {
+        let Some(search) = self.search.as_mut() else {
+            return;
+        };
+
+        let action = self.keymap.action(Context::Popup, key);
+        if self.keymap.action(Context::Input, key) == Some(Action::SearchHistory) {
+            search.older(&self.history);
+            return;
+        }
+
+        match (action, key.code) {
+            (Some(Action::Unfocus), _) => self.search = None,
+            (Some(Action::SelectPrev), _) => search.older(&self.history),
+            (Some(Action::Submit), _) | (_, KeyCode::Tab) => {
+                if let Some(entry) = search.found.and_then(|i| self.history.entries.get(i)) {
+                    self.textarea.set_text(entry);
+                }
+                self.search = None;
+            }
+            (_, KeyCode::Backspace) => {
+                search.query.pop();
+                search.update(&self.history);
+            }
+            (_, KeyCode::Char(c)) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                search.query.push(c);
+                search.update(&self.history);
+            }
+            _ => {}
+        }
+    }
+
+    /// Recalls an older (or newer) prompt when the cursor is on the first (or last) line, returns
+    /// false when the key should move the cursor instead.
+    fn recall_history(&mut self, older: bool) -> bool {
+        let (row, _) = self.textarea.area.cursor();
+        let last_row = self.textarea.area.lines().len() - 1;
+
+        let entry = match older {
+            true if row == 0 => self.history.older(&self.textarea.text()),
+            false if row == last_row => self.history.newer(),
+            _ => return false,
+        };
+
+        match entry {
+            Some(entry) => {
+                let entry = entry.to_string();
+                self.textarea.set_text(&entry);
+                true
+            }
+            None => false,
         }
     }
 
     pub fn handle_events(&mut self, event: Event) -> Result<()> {
         if let Event::Key(key) = event {
-            if key.code == KeyCode::Char('n') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            if self.search.is_some() {
+                self.handle_search_events(key);
+                return Ok(());
+            }
+
+            let action = self.keymap.action(Context::Input, key);
+
+            let vim_normal = self
+                .textarea
+                .vim
+                .as_ref()
+                .is_some_and(|vim| vim.mode != Mode::Insert);
+            if action == Some(Action::SearchHistory) && !vim_normal {
+                self.open_search();
+                return Ok(());
+            }
+
+            if !self.textarea.completions.is_empty() {
+                match key.code {
+                    KeyCode::Tab => {
+                        self.textarea.accept_completion();
+                        return Ok(());
+                    }
+                    KeyCode::Up | KeyCode::Down => {
+                        self.textarea.select_completion(key.code == KeyCode::Down);
+                        return Ok(());
+                    }
+                    _ => {}
+                }
+            }
+
+            if action == Some(Action::InsertNewline) {
                 self.textarea.area.insert_newline();
                 return Ok(());
             }
 
-            if key.code == KeyCode::Enter {
-                let txt = self.textarea.clear();
-                self.push_user_message(txt);
-                self.start_generating();
+            if (key.code == KeyCode::Up || key.code == KeyCode::Down)
+                && self.recall_history(key.code == KeyCode::Up)
+            {
                 return Ok(());
             }
 
-            self.textarea.area.input(key);
+            if action == Some(Action::Submit) {
+                self.submit();
+                return Ok(());
+            }
+
+            self.textarea.input(key);
         }
 
         Ok(())
@@ -114,48 +383,127 @@ impl<'a> Chat<'a> {
             return;
         }
         self.scroll_state -= 1;
+        self.follow = false;
     }
 
+    /// scrolls down, reaching the bottom turns the follow mode back on
     pub fn scroll_down(&mut self) {
         if self.scroll_state > self.scroll_area {
             return;
         }
         self.scroll_state += 1;
+        if self.scroll_state >= self.scroll_area {
+            self.scroll_to_bottom();
+        }
     }
 
-    pub fn select_next(&mut self) {
-        if self.messages.is_empty() {
-            return;
-        }
+    /// jumps to the end of the chat and follows it from now on
+    pub fn scroll_to_bottom(&mut self) {
+        self.follow = true;
+        self.unseen = false;
+        self.scroll_state = self.scroll_area;
+    }
 
-        if self.selected_message_id + 1 >= self.messages.len() {
-            return;
+    fn set_selected(&mut self, index: usize, is_selected: bool) {
+        let path = self.conversation.session.tree.active_path();
+        if let Some(id) = path.get(index) {
+            self.views.entry(*id).or_default().is_selected = is_selected;
         }
+    }
 
-        self.messages[self.selected_message_id].is_selected = false;
-        self.selected_message_id += 1;
-        self.messages[self.selected_message_id].is_selected = true;
+    /// selects the message after the selected one, the first message when none is selected
+    pub fn select_next(&mut self) {
+        let path = self.conversation.session.tree.active_path();
+        let next = match self.selected_message() {
+            Some(_) => (self.selected_message_id + 1).min(path.len().saturating_sub(1)),
+            None => 0,
+        };
+        self.select_only(path.get(next).copied());
     }
 
+    /// selects the message before the selected one, the last message when none is selected
     pub fn select_prev(&mut self) {
-        if self.messages.is_empty() || self.selected_message_id == 0 {
-            return;
+        let path = self.conversation.session.tree.active_path();
+        let prev = match self.selected_message() {
+            Some(_) => self.selected_message_id.saturating_sub(1),
+            None => path.len().saturating_sub(1),
+        };
+        self.select_only(path.get(prev).copied());
+    }
+
+    /// the first selected message of the active path
+    pub fn selected_message(&self) -> Option<Uuid> {
+        self.conversation
+            .session
+            .tree
+            .active_path()
+            .into_iter()
+            .find(|id| self.views.get(id).is_some_and(|view| view.is_selected))
+    }
+
+    /// selects the message `id` alone, or no message when None
+    pub fn select_only(&mut self, id: Option<Uuid>) {
+        self.views
+            .values_mut()
+            .for_each(|view| view.is_selected = false);
+        let path = self.conversation.session.tree.active_path();
+        if let Some(id) = id
+            && let Some(index) = path.iter().position(|p| *p == id)
+        {
+            self.selected_message_id = index;
+            self.views.entry(id).or_default().is_selected = true;
         }
+    }
 
-        self.messages[self.selected_message_id].is_selected = false;
-        self.selected_message_id -= 1;
-        self.messages[self.selected_message_id].is_selected = true;
+    /// the message of the active path drawn at `x`, `y`
+    pub fn message_at(&self, x: u16, y: u16) -> Option<Uuid> {
+        self.conversation
+            .session
+            .tree
+            .active_path()
+            .into_iter()
+            .filter(|id| self.views.get(id).is_some_and(|view| view.is_within(x, y)))
+            .last()
     }
 
-    pub fn set_scroll_area(&mut self, scroll_area: usize) -> () {
-        self.scroll_area = scroll_area;
+    /// Switches the selected message to its next (or previous) alternative and returns the id
+    /// of the alternative that is now displayed.
+    pub fn select_alternative(&mut self, forward: bool) -> Option<Uuid> {
+        let id = self.selected_message()?;
+        let new_id = self
+            .conversation
+            .session
+            .tree
+            .select_sibling(&id, forward)?;
+
+        self.views.entry(id).or_default().is_selected = false;
+        self.views.entry(new_id).or_default().is_selected = true;
+
+        Some(new_id)
     }
 
-    pub fn push_user_message(&mut self, value: String) -> usize {
-        let idx = self.messages.len();
-        let msg = Message::new(idx, value, false, Role::User);
-        self.messages.push(msg);
-        idx
+    /// Loads the selected user message into the textarea, submitting it will create an
+    /// alternative to it instead of overwriting it. Returns whether the edit has started.
+    pub fn edit_selected(&mut self) -> bool {
+        let Some(msg) = self
+            .selected_message()
+            .and_then(|id| self.conversation.session.tree.get(&id))
+        else {
+            return false;
+        };
+
+        if msg.role != Role::User {
+            return false;
+        }
+
+        self.textarea.clear();
+        self.textarea.area.insert_str(&msg.text);
+        self.editing = Some(msg.id);
+        true
+    }
+
+    pub fn set_scroll_area(&mut self, scroll_area: usize) {
+        self.scroll_area = scroll_area;
     }
 
     pub fn render_vertical_scrollbar(
@@ -178,68 +526,68 @@ impl<'a> Chat<'a> {
         StatefulWidget::render(scrollbar, area, buf, &mut scrollbar_state);
     }
 
-    fn generate_req_body(&self) -> OllamaReqBody {
-        let mut response = OllamaReqBody::default();
+    /// tells that the chat grew below the view, on the last line of `area`
+    fn render_unseen_indicator(&self, area: Rect, buf: &mut Buffer) {
+        let text = " ↓ new content below ";
+        let width = (text.chars().count() as u16).min(area.width);
+        let rect = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.bottom().saturating_sub(1),
+            width,
+            height: area.height.min(1),
+        };
+        Clear.render(rect, buf);
+        Paragraph::new(text)
+            .style(self.theme.highlight)
+            .render(rect, buf);
+    }
 
-        self.messages.iter().for_each(|item| {
-            let msg = Msg {
-                role: item.role.to_lower_string(),
-                content: item.text.clone(),
-            };
-            response.messages.push(msg);
-        });
+    /// asks for a reply to the displayed conversation and follows it as it streams
+    pub fn start_generating(&mut self) {
+        self.scroll_to_bottom();
+        self.conversation.start_generating();
+    }
 
-        response
+    /// Regenerates the reply `id`, see `Conversation::regenerate`. The new reply takes over the
+    /// selection of the old one.
+    pub fn regenerate(&mut self, id: &Uuid, settings: Option<GenerationSettings>) -> bool {
+        let Some(new_id) = self.conversation.regenerate(id, settings) else {
+            return false;
+        };
+
+        let view = self.views.entry(*id).or_default();
+        let is_selected = view.is_selected;
+        view.is_selected = false;
+        self.views.entry(new_id).or_default().is_selected = is_selected;
+        true
     }
 
-    /// This method is going to spawn an async thread and it will update the response String
-    /// with the latest response, once it is done, it calls a method that will lock the response
-    /// of the LLM
-    pub fn start_generating(&mut self) {
-        let body = self.generate_req_body();
-        let tx = self.tx.clone();
-
-        self.messages.push(Message::new(
-            self.messages.len(),
-            String::from(""),
-            true,
-            Role::System,
-        ));
-
-        task::spawn(async move {
-            let json = serde_json::to_string(&body).unwrap();
-
-            let client = Client::new();
-            let bytes = client
-                .post("http://localhost:11434/api/chat")
-                .body(json)
-                .header("Content-Type", "application/json")
-                .send()
-                .await
-                .unwrap();
-
-            let mut stream = bytes.bytes_stream();
-
-            while let Some(chunk) = stream.next().await {
-                if let Ok(chunk) = chunk {
-                    let text = String::from_utf8_lossy(chunk.as_ref()).to_string();
-                    if let Ok(parsed) = serde_json::from_str::<Chunk>(&text) {
-                        tx.send(parsed).unwrap();
-                    }
-                }
-            }
-        });
+    /// Waits for the replies being generated, see `Conversation::receive`.
+    pub async fn receive(&mut self) {
+        self.conversation.receive().await;
+    }
+
+    /// writes what was received so far without waiting, returns false when there was nothing
+    pub fn receive_pending(&mut self) -> bool {
+        self.conversation.receive_pending()
+    }
+
+    /// the prompts submitted and the replies finished since the last call, see
+    /// `ConversationEvent`
+    pub fn take_events(&mut self) -> Vec<ConversationEvent> {
+        self.conversation.take_events()
     }
 }
 
-impl<'a> Widget for &mut Chat<'a> {
-    fn render(self, area: Rect, buf: &mut Buffer) {
-        if let Ok(chan_msg) = self.rx.try_recv() {
-            if let Some(last_msg) = self.messages.last_mut() {
-                last_msg.text.push_str(chan_msg.message.content.as_ref());
-            }
-        }
+/// Draws a `Chat`: the messages of the active path, the textarea and the popups of the input.
+/// The theme and the layout of the messages are settings of the chat.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ChatView;
 
+impl StatefulWidget for ChatView {
+    type State = Chat;
+
+    fn render(self, area: Rect, buf: &mut Buffer, chat: &mut Chat) {
         let layout = Layout::horizontal([
             Constraint::Percentage(5),
             Constraint::Percentage(90),
@@ -247,7 +595,9 @@ impl<'a> Widget for &mut Chat<'a> {
         ])
         .split(area);
 
-        Block::bordered().render(layout[1], buf);
+        Block::bordered()
+            .style(chat.theme.border)
+            .render(layout[1], buf);
         let chat_inner = Block::bordered().inner(layout[1]);
 
         let chat_inner_layout =
@@ -257,20 +607,66 @@ impl<'a> Widget for &mut Chat<'a> {
         let chat_inner = chat_inner_layout[0];
         let chat_textarea = chat_inner_layout[1];
 
-        let total_height: u16 = self.messages.iter().map(|m| m.text_height).sum();
-        self.height = total_height as usize + OFFSET;
+        let tree = &chat.conversation.session.tree;
+        let path = tree.active_path();
+        for id in path.iter() {
+            let Some(msg) = tree.get(id) else {
+                continue;
+            };
+            let view = chat.views.entry(*id).or_default();
+            view.theme = chat.theme;
+            if view.user_name != chat.user_name {
+                view.user_name = chat.user_name.clone();
+            }
+            // the streamed message grows every frame, its height is needed to follow it
+            if view.markdown != chat.markdown
+                || view.compact != chat.compact
+                || view.text_height == 0
+                || msg.generating
+            {
+                view.markdown = chat.markdown;
+                view.compact = chat.compact;
+                view.refresh_height(msg);
+            }
+        }
+        chat.textarea.theme = chat.theme;
+
+        let total_height: u16 = path
+            .iter()
+            .filter_map(|id| chat.views.get(id))
+            .map(|view| view.text_height)
+            .sum();
+        let previous_height = chat.height;
+        chat.height = total_height as usize + OFFSET;
+
+        let bottom = chat.height.saturating_sub(chat_inner.height as usize);
+        if chat.follow {
+            chat.scroll_state = bottom;
+        } else if chat.height > previous_height && chat.scroll_state < bottom {
+            chat.unseen = true;
+        }
 
-        let scroll_top = self.scroll_state as i32;
+        let scroll_top = chat.scroll_state as i32;
         let visible_height = chat_inner.height as i32;
 
         let mut y = chat_inner.y as i32;
-        let mut new_id = self.selected_message_id;
-        let len = self.messages.len();
-
-        self.textarea.render(chat_textarea, buf);
+        let mut new_id = chat.selected_message_id;
+        let len = path.len();
+        // the selection only moves along with the scroll when there is one
+        let has_selection = path
+            .iter()
+            .any(|id| chat.views.get(id).is_some_and(|view| view.is_selected));
+
+        chat.textarea.render(chat_textarea, buf);
+
+        for id in path.iter() {
+            let (position, count) = tree.siblings(id);
+            let (Some(msg), Some(view)) = (tree.get(id), chat.views.get_mut(id)) else {
+                continue;
+            };
+            view.set_siblings(position, count);
 
-        for item in self.messages.iter_mut() {
-            let h = item.text_height as i32;
+            let h = view.text_height as i32;
 
             let msg_top = y - chat_inner.y as i32;
             let msg_bottom = msg_top + h;
@@ -287,47 +683,54 @@ impl<'a> Widget for &mut Chat<'a> {
             // visible, and we can say that 2 is the visible_top for the same reason
 
             let visible_top = scroll_top.max(msg_top);
-            let visible_bottom = (scroll_top + visible_height) as i32;
+            let visible_bottom = scroll_top + visible_height;
 
             // this is the height that is still on screen
             let clip_height = (msg_bottom.min(visible_bottom) - visible_top).max(0);
             let clip_start = (visible_top - msg_top).max(0);
 
             if clip_height > 0 {
+                let (x, width) = horizontal_bounds(msg, view, chat_inner, chat.bubbles);
                 let rect = Rect {
-                    x: chat_inner.x,
-                    y: (chat_inner.y as i32 + visible_top - scroll_top as i32) as u16,
-                    width: chat_inner.width,
+                    x,
+                    y: (chat_inner.y as i32 + visible_top - scroll_top) as u16,
+                    width,
                     height: clip_height as u16,
                 };
 
-                item.set_skip_lines(clip_start as u16);
+                view.set_skip_lines(clip_start as u16);
 
-                item.render(rect, buf);
+                Message::new(msg).render(rect, buf, view);
             }
 
             let msg_top_hit_bottom = msg_top + MARGIN == visible_bottom;
-            if msg_top_hit_bottom && item.is_selected && item.index > 0 {
+            if msg_top_hit_bottom && view.is_selected && msg.index > 0 {
                 new_id -= 1;
-                item.is_selected = false;
+                view.is_selected = false;
             }
 
             let msg_bottom_hit_top = msg_bottom == visible_top + MARGIN;
-            if msg_bottom_hit_top && item.is_selected {
-                if item.index + 1 < len {
-                    new_id += 1;
-                    item.is_selected = false;
-                }
+            if msg_bottom_hit_top && view.is_selected && msg.index + 1 < len {
+                new_id += 1;
+                view.is_selected = false;
             }
 
             y += h;
         }
 
-        self.selected_message_id = new_id;
-        if !self.messages.is_empty() {
-            self.messages[new_id].is_selected = true;
+        if has_selection {
+            chat.selected_message_id = new_id;
+            chat.set_selected(new_id, true);
+        }
+
+        if chat.unseen && !chat.follow {
+            chat.render_unseen_indicator(chat_inner, buf);
         }
 
-        self.render_vertical_scrollbar(layout[2], buf, chat_inner.height as usize);
+        chat.textarea.render_completions(chat_textarea, buf);
+        if let Some(search) = chat.search.as_ref() {
+            search.render(&chat.history, &chat.theme, chat_textarea, buf);
+        }
+        chat.render_vertical_scrollbar(layout[2], buf, chat_inner.height as usize);
     }
 }
@@ -0,0 +1,157 @@
+use ratatui::crossterm::event::{Event, KeyEventKind};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::Modifier,
+    text::{Line, Span},
+    widgets::{Block, Clear, Paragraph, Widget},
+};
+
+use crate::{
+    actions::Action,
+    commands::COMMANDS,
+    config::Config,
+    keymap::{Context, Keymap},
+    theme::Theme,
+    utils::centered_rect,
+};
+
+/// A scrollable overlay listing the keys of the focused component, generated from the active
+/// keymap, and the slash commands.
+#[derive(Debug)]
+pub struct Help {
+    lines: Vec<Line<'static>>,
+    scroll: u16,
+}
+
+/// keys handled by the textarea itself, they cannot be remapped
+const INPUT_KEYS: &[(&str, &str)] = &[
+    ("Tab", "Complete a /command or an @path"),
+    (
+        "Up, Down",
+        "Recall the previous prompts, on the first or last line",
+    ),
+];
+
+impl Help {
+    /// Lists the keys active in `context`, followed by the slash commands and `usage`, the
+    /// command line usage.
+    pub fn new(keymap: &Keymap, context: Context, usage: &str, theme: &Theme) -> Self {
+        let heading = theme.accent.add_modifier(Modifier::BOLD);
+        let description = theme.muted;
+        let entry = |left: String, right: String| {
+            Line::from(vec![
+                Span::raw(format!("  {:<24}", left)),
+                Span::styled(right, description),
+            ])
+        };
+
+        let focus = match context {
+            Context::Global => "the chat",
+            Context::Input => "the input",
+            Context::Message => "the selected message",
+            Context::Popup => "popups",
+        };
+        let mut lines = vec![Line::from(Span::styled(
+            format!("Keys of {}", focus),
+            heading,
+        ))];
+
+        let mut actions: Vec<Action> = Vec::new();
+        for layer in Keymap::layers(context) {
+            for binding in keymap.bindings(*layer) {
+                if !actions.contains(&binding.action) {
+                    actions.push(binding.action);
+                }
+            }
+        }
+        for action in actions {
+            let keys: Vec<String> = Keymap::layers(context)
+                .iter()
+                .flat_map(|layer| keymap.keys(*layer, action))
+                .map(|key| key.to_string())
+                .collect();
+            lines.push(entry(keys.join(", "), action.description()));
+        }
+        if context == Context::Input {
+            for (keys, action) in INPUT_KEYS {
+                lines.push(entry(keys.to_string(), action.to_string()));
+            }
+        }
+
+        lines.push(Line::default());
+        lines.push(Line::from(Span::styled("Slash commands", heading)));
+        for command in COMMANDS {
+            lines.push(entry(command.synopsis(), command.description.to_string()));
+        }
+
+        lines.push(Line::default());
+        lines.push(Line::from(Span::styled("Command line", heading)));
+        lines.extend(usage.lines().map(|line| Line::from(format!("  {}", line))));
+
+        lines.push(Line::default());
+        lines.push(Line::from(Span::styled(
+            format!("Keys can be changed in {}", Config::path().display()),
+            theme.muted,
+        )));
+
+        Self { lines, scroll: 0 }
+    }
+
+    /// returns true when the overlay should be closed
+    pub fn handle_events(&mut self, event: Event, keymap: &Keymap) -> bool {
+        let Event::Key(key) = event else {
+            return false;
+        };
+
+        if key.kind != KeyEventKind::Press {
+            return false;
+        }
+
+        match keymap.action(Context::Popup, key) {
+            Some(Action::Unfocus) | Some(Action::Submit) => return true,
+            Some(Action::SelectNext) => self.scroll_down(),
+            Some(Action::SelectPrev) => self.scroll_up(),
+            _ => {}
+        }
+
+        match keymap.action(Context::Global, key) {
+            Some(Action::Help) => true,
+            Some(Action::ScrollDown) => {
+                self.scroll_down();
+                false
+            }
+            Some(Action::ScrollUp) => {
+                self.scroll_up();
+                false
+            }
+            _ => false,
+        }
+    }
+
+    fn scroll_down(&mut self) {
+        if (self.scroll as usize) + 1 < self.lines.len() {
+            self.scroll += 1;
+        }
+    }
+
+    fn scroll_up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+}
+
+impl Widget for &mut Help {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let area = centered_rect(area, 70, 80);
+        Clear.render(area, buf);
+
+        Paragraph::new(self.lines.clone())
+            .scroll((self.scroll, 0))
+            .block(
+                Block::bordered()
+                    .title("Keybindings")
+                    .title_bottom("Up/Down to scroll, Esc to close"),
+            )
+            .render(area, buf);
+    }
+}
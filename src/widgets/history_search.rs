@@ -0,0 +1,67 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    text::{Line, Span},
+    widgets::{Block, Clear, Paragraph, Widget},
+};
+
+use crate::{history::History, theme::Theme};
+
+/// Reverse incremental search through the prompt history, like Ctrl+R in a shell.
+#[derive(Debug, Default)]
+pub struct HistorySearch {
+    pub query: String,
+    /// index of the matching entry in the history
+    pub found: Option<usize>,
+}
+
+impl HistorySearch {
+    /// looks for the query again, starting from the most recent entry
+    pub fn update(&mut self, history: &History) {
+        self.found = history
+            .search(&self.query, history.entries.len())
+            .map(|(i, _)| i);
+    }
+
+    /// moves to the next older entry matching the query, stays on the current one if there is
+    /// none
+    pub fn older(&mut self, history: &History) {
+        let before = self.found.unwrap_or(history.entries.len());
+        if let Some((i, _)) = history.search(&self.query, before) {
+            self.found = Some(i);
+        }
+    }
+
+    /// renders the search right above `area`, the area of the textarea
+    pub fn render(&self, history: &History, theme: &Theme, area: Rect, buf: &mut Buffer) {
+        let popup = Rect {
+            x: area.x,
+            y: area.y.saturating_sub(3),
+            width: area.width,
+            height: 3.min(area.y),
+        };
+
+        let found = self.found.and_then(|i| history.entries.get(i));
+        let (title, style) = match found {
+            Some(_) => ("reverse-i-search", theme.success),
+            None => ("failing reverse-i-search", theme.error),
+        };
+
+        let entry = found
+            .map(|entry| entry.replace('\n', " ⏎ "))
+            .unwrap_or_default();
+        let line = Line::from(vec![
+            Span::styled(format!("`{}': ", self.query), style),
+            Span::raw(entry),
+        ]);
+
+        Clear.render(popup, buf);
+        Paragraph::new(line)
+            .block(
+                Block::bordered()
+                    .title(title)
+                    .title_bottom("Ctrl+R older, Enter to use, Esc to cancel"),
+            )
+            .render(popup, buf);
+    }
+}
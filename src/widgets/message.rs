@@ -1,113 +1,106 @@
-use log::info;
-use ratatui::crossterm::event::{Event, KeyCode};
 use ratatui::{
     buffer::Buffer,
     layout::{Constraint, Layout, Rect},
-    style::{Color, Style},
+    style::Modifier,
+    text::{Line, Span, Text},
     widgets::{
         Block, Borders, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, StatefulWidget,
         Widget,
     },
 };
-use std::io::Result;
-use uuid::Uuid;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::utils::{get_height, get_longest_string};
+use crate::{
+    conversation::{ChatMessage, Role},
+    session::format_time,
+    theme::Theme,
+    utils::{get_height, get_longest_string},
+};
 
 pub const OFFSET: usize = 2;
 
-#[derive(Debug)]
-pub enum Role {
-    User,
-    System,
-}
-
-impl Role {
-    pub fn to_lower_string(&self) -> String {
-        match self {
-            Role::User => String::from("user"),
-            Role::System => String::from("system"),
-        }
-    }
-
-    pub fn to_string(&self) -> String {
-        match self {
-            Role::User => String::from("User"),
-            Role::System => String::from("System"),
-        }
-    }
-}
-
-#[derive(Debug)]
-pub struct Message {
-    pub id: Uuid,
-    pub index: usize,
+/// Where a message is on screen and how it is drawn, kept by the chat for every message it
+/// displays.
+#[derive(Debug, Default)]
+pub struct MessageState {
     pub is_selected: bool,
-    pub role: Role,
-    pub text: String,
     pub text_height: u16,
     /// horizontal scroll position
     pub scroll_state: usize,
     /// horizontal scroll area
     pub scroll_area: usize,
     pub skip_lines: u16,
-    pub generating: bool,
+    /// position among the alternatives and how many there are, used to render the "< 1/2 >"
+    /// controls
+    pub siblings: (usize, usize),
+    /// renders the text as markdown, set by the chat
+    pub markdown: bool,
+    /// set by the chat
+    pub theme: Theme,
+    /// drops the borders, the header takes a single line, set by the chat
+    pub compact: bool,
+    /// the name shown in the header of the prompts, set by the chat
+    pub user_name: String,
     pub x: u16,
     pub y: u16,
     pub width: u16,
     pub height: u16,
 }
 
-impl Message {
-    pub fn new(id: usize, text: String, generating: bool, role: Role) -> Self {
-        let height = get_height(&text) as usize;
-        Message {
-            id: Uuid::new_v4(),
-            index: id,
-            is_selected: false,
-            text,
-            text_height: height as u16,
-            scroll_state: 0,
-            scroll_area: 0,
-            role,
-            skip_lines: 0,
-            generating: generating,
-            x: 0,
-            y: 0,
-            height: 0,
-            width: 0,
+impl MessageState {
+    /// recomputes `text_height` from the text of `msg`, its attachment chips and its tool calls
+    pub fn refresh_height(&mut self, msg: &ChatMessage) {
+        let chips = if msg.attachments.is_empty() { 0 } else { 1 };
+        let calls = msg.tool_calls.len() as u16;
+        let height = match (self.markdown, self.compact) {
+            (true, false) => {
+                let lines = tui_markdown::from_str(&msg.text).lines.len();
+                (lines + OFFSET).max(3) as u16
+            }
+            (false, false) => get_height(&msg.text),
+            // the header and at least one line of text
+            (true, true) => tui_markdown::from_str(&msg.text).lines.len().max(1) as u16 + 1,
+            (false, true) => msg.text.split('\n').count() as u16 + 1,
+        };
+        self.text_height = height + chips + calls;
+    }
+
+    /// the title of `msg`: who wrote it and when
+    pub fn header(&self, msg: &ChatMessage) -> String {
+        let author = match msg.role {
+            Role::User if self.user_name.is_empty() => "you",
+            Role::User => self.user_name.as_str(),
+            Role::Assistant => msg.model.as_deref().unwrap_or("assistant"),
+            Role::System => "system prompt",
+            Role::Error => "error",
+            Role::Tool => msg.tool_name.as_deref().unwrap_or("tool"),
+        };
+
+        let Some(created) = msg.created else {
+            return format!(" {} ", author);
+        };
+        let time = format_time(UNIX_EPOCH + Duration::from_secs(created));
+        let today = format_time(SystemTime::now());
+        match time.split_once(' ') {
+            // only the time for the messages of today
+            Some((date, clock)) if today.starts_with(date) => format!(" {} · {} ", author, clock),
+            _ => format!(" {} · {} ", author, time),
         }
     }
 
-    pub fn prepare(&mut self, area: Rect) {
-        let width = get_longest_string(&self.text);
+    fn prepare(&mut self, msg: &ChatMessage, area: Rect) {
+        let width = get_longest_string(&msg.text);
         let viewport_width = area.width as usize;
         let scroll_area = width.saturating_sub(viewport_width);
-        let height = get_height(&self.text) as usize;
 
         self.scroll_area = scroll_area;
-        self.text_height = height as u16;
+        self.refresh_height(msg);
         self.x = area.x;
         self.y = area.y;
         self.width = area.width;
         self.height = area.height;
     }
 
-    pub fn handle_events(&mut self, event: Event) -> Result<()> {
-        if let Event::Key(key) = event {
-            if key.code == KeyCode::Char('l') {
-                self.scroll_right();
-                return Ok(());
-            }
-            if key.code == KeyCode::Char('h') {
-                self.scroll_left();
-                return Ok(());
-            }
-        }
-
-        Ok(())
-    }
-
     pub fn is_within(&self, x: u16, y: u16) -> bool {
         let within_x = x >= self.x && x < self.x + self.width;
         let within_y = y >= self.y && y < self.y + self.text_height;
@@ -118,7 +111,11 @@ impl Message {
         self.skip_lines = skip_lines;
     }
 
-    pub fn scroll_right(&mut self) -> () {
+    pub fn set_siblings(&mut self, position: usize, count: usize) {
+        self.siblings = (position, count);
+    }
+
+    pub fn scroll_right(&mut self) {
         if self.scroll_state > self.scroll_area {
             return;
         }
@@ -145,41 +142,108 @@ impl Message {
     }
 }
 
-impl Widget for &mut Message {
-    fn render(self, area: Rect, buf: &mut Buffer) {
-        self.prepare(area);
-        let txt = self.text.clone();
+/// Draws a message of the conversation in a block titled with its header.
+pub struct Message<'a> {
+    msg: &'a ChatMessage,
+}
+
+impl<'a> Message<'a> {
+    pub fn new(msg: &'a ChatMessage) -> Self {
+        Self { msg }
+    }
+
+    fn chips(&self, theme: &Theme) -> Line<'static> {
+        let mut spans = Vec::new();
+        for attachment in self.msg.attachments.iter() {
+            let style = match attachment.error {
+                Some(_) => theme.error.add_modifier(Modifier::REVERSED),
+                None => theme.chip,
+            };
+            spans.push(Span::styled(format!(" {} ", attachment.label()), style));
+            spans.push(Span::raw(" "));
+        }
+        Line::from(spans)
+    }
+
+    /// a line for each tool the model asked to call
+    fn tool_calls(&self, theme: &Theme) -> Vec<Line<'static>> {
+        self.msg
+            .tool_calls
+            .iter()
+            .map(|call| Line::styled(format!("→ {}", call), theme.tool))
+            .collect()
+    }
+}
+
+impl<'a> StatefulWidget for Message<'a> {
+    type State = MessageState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut MessageState) {
+        state.prepare(self.msg, area);
+        let mut txt = if state.markdown {
+            tui_markdown::from_str(&self.msg.text)
+        } else {
+            Text::from(self.msg.text.as_str())
+        };
+        if !self.msg.attachments.is_empty() {
+            txt.push_line(self.chips(&state.theme));
+        }
+        for line in self.tool_calls(&state.theme) {
+            txt.push_line(line);
+        }
 
-        let scroll_or_zero = self.scroll_area.min(1) as u16;
+        let scroll_or_zero = state.scroll_area.min(1) as u16;
 
         let layout = Layout::vertical([
             Constraint::Length(scroll_or_zero),
-            Constraint::Length(self.text_height - self.skip_lines),
+            Constraint::Length(state.text_height - state.skip_lines),
         ])
         .split(area);
 
-        self.render_horizontal_scrollbar(layout[0], buf);
+        state.render_horizontal_scrollbar(layout[0], buf);
 
-        let selected_style = if self.is_selected {
-            Style::default().fg(Color::LightGreen)
+        let selected_style = if state.is_selected {
+            state.theme.selected
         } else {
-            Style::default().fg(Color::White)
+            state.theme.role(&self.msg.role)
         };
-        let mut block = Block::bordered().style(selected_style).title("System");
-        if self.skip_lines > 0 {
-            block = Block::default()
-                .style(selected_style)
-                .borders(Borders::LEFT | Borders::RIGHT | Borders::BOTTOM);
-        }
-
-        let scroll = if self.skip_lines == 1 {
-            0
+        let borders = if state.compact {
+            Borders::NONE
         } else {
-            self.skip_lines
+            Borders::ALL
         };
+        let header = if state.compact {
+            Line::styled(
+                state.header(self.msg),
+                selected_style.add_modifier(Modifier::BOLD),
+            )
+        } else {
+            Line::from(state.header(self.msg))
+        };
+        let mut block = Block::default()
+            .borders(borders)
+            .style(selected_style)
+            .title(header);
+        let (position, count) = state.siblings;
+        if count > 1 {
+            block = block
+                .title_top(Line::from(format!("< {}/{} >", position + 1, count)).right_aligned());
+        }
+
+        if state.skip_lines > 0 {
+            let borders = if state.compact {
+                Borders::NONE
+            } else {
+                Borders::LEFT | Borders::RIGHT | Borders::BOTTOM
+            };
+            block = Block::default().style(selected_style).borders(borders);
+        }
+
+        // the first skipped line is the top border, or the header when compact
+        let scroll = state.skip_lines.saturating_sub(1);
 
         Paragraph::new(txt)
-            .scroll((scroll, self.scroll_state as u16))
+            .scroll((scroll, state.scroll_state as u16))
             .block(block)
             .render(layout[1], buf);
     }
@@ -1,3 +1,10 @@
 pub mod chat;
+pub mod help;
+pub mod history_search;
 pub mod message;
+pub mod palette;
+pub mod save_dialog;
+pub mod status;
 pub mod textarea;
+pub mod tool_dialog;
+pub mod vim;
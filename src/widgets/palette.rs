@@ -0,0 +1,187 @@
+use ratatui::crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Layout, Rect},
+    text::{Line, Span},
+    widgets::{Block, Clear, List, ListItem, ListState, Paragraph, StatefulWidget, Widget},
+};
+
+use crate::{
+    actions::Action,
+    keymap::{Context, Keymap},
+    theme::Theme,
+    utils::centered_rect,
+};
+
+/// An entry of the palette.
+#[derive(Debug, Clone)]
+pub struct PaletteItem<T> {
+    pub label: String,
+    /// shown on the right of the label, like the key bound to an action
+    pub detail: String,
+    pub value: T,
+}
+
+/// What the palette wants the app to do after handling an event.
+#[derive(Debug, PartialEq)]
+pub enum PaletteEvent<T> {
+    None,
+    Close,
+    Selected(T),
+}
+
+/// A modal list filtered by fuzzy matching what the user types, used for the command palette
+/// and to pick a session.
+#[derive(Debug)]
+pub struct Palette<T> {
+    pub title: String,
+    pub query: String,
+    pub items: Vec<PaletteItem<T>>,
+    /// indexes of the items matching the query, best match first
+    pub filtered: Vec<usize>,
+    pub selected: usize,
+    pub theme: Theme,
+}
+
+impl<T: Clone> Palette<T> {
+    pub fn new(title: impl Into<String>, items: Vec<PaletteItem<T>>) -> Self {
+        let mut palette = Self {
+            title: title.into(),
+            query: String::new(),
+            items,
+            filtered: Vec::new(),
+            selected: 0,
+            theme: Theme::default(),
+        };
+        palette.filter();
+        palette
+    }
+
+    fn filter(&mut self) {
+        let mut scored: Vec<(i64, usize)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, item)| fuzzy_score(&self.query, &item.label).map(|score| (score, i)))
+            .collect();
+
+        // stable sort, items with the same score keep their order
+        scored.sort_by_key(|(score, _)| -score);
+        self.filtered = scored.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+    }
+
+    pub fn selected_item(&self) -> Option<&PaletteItem<T>> {
+        self.filtered
+            .get(self.selected)
+            .and_then(|i| self.items.get(*i))
+    }
+
+    pub fn handle_events(&mut self, event: Event, keymap: &Keymap) -> PaletteEvent<T> {
+        let Event::Key(key) = event else {
+            return PaletteEvent::None;
+        };
+
+        if key.kind != KeyEventKind::Press {
+            return PaletteEvent::None;
+        }
+
+        match keymap.action(Context::Popup, key) {
+            Some(Action::Unfocus) => return PaletteEvent::Close,
+            Some(Action::Submit) => {
+                return match self.selected_item() {
+                    Some(item) => PaletteEvent::Selected(item.value.clone()),
+                    None => PaletteEvent::None,
+                };
+            }
+            Some(Action::SelectNext) => self.select_next(),
+            Some(Action::SelectPrev) => self.select_prev(),
+            _ => match key.code {
+                KeyCode::Backspace => {
+                    self.query.pop();
+                    self.filter();
+                }
+                KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.query.push(c);
+                    self.filter();
+                }
+                _ => {}
+            },
+        }
+
+        PaletteEvent::None
+    }
+
+    fn select_next(&mut self) {
+        if self.selected + 1 < self.filtered.len() {
+            self.selected += 1;
+        }
+    }
+
+    fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
+
+/// Scores how well `query` matches `text`: every character of the query has to appear in the
+/// text in order, ignoring case. Consecutive characters and matches at the start of a word
+/// score higher. Returns None when the text does not match.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+
+    for c in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = position + text[position..].iter().position(|t| *t == c)?;
+
+        score += 1;
+        if previous.is_some_and(|p| p + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+
+        previous = Some(found);
+        position = found + 1;
+    }
+
+    Some(score)
+}
+
+impl<T: Clone> Widget for &mut Palette<T> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let area = centered_rect(area, 60, 60);
+        Clear.render(area, buf);
+
+        let layout = Layout::vertical([Constraint::Length(3), Constraint::Min(0)]).split(area);
+
+        Paragraph::new(format!("> {}", self.query))
+            .block(Block::bordered().title(self.title.as_str()))
+            .render(layout[0], buf);
+
+        let width = layout[1].width.saturating_sub(2) as usize;
+        let items: Vec<ListItem> = self
+            .filtered
+            .iter()
+            .map(|i| &self.items[*i])
+            .map(|item| {
+                let padding =
+                    width.saturating_sub(item.label.chars().count() + item.detail.chars().count());
+                ListItem::new(Line::from(vec![
+                    Span::raw(item.label.clone()),
+                    Span::raw(" ".repeat(padding)),
+                    Span::styled(item.detail.clone(), self.theme.muted),
+                ]))
+            })
+            .collect();
+
+        let list = List::new(items)
+            .block(Block::bordered().title_bottom("Enter to run, Esc to close"))
+            .highlight_style(self.theme.highlight);
+
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        StatefulWidget::render(list, layout[1], buf, &mut state);
+    }
+}
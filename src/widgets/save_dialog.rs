@@ -0,0 +1,247 @@
+use ratatui::crossterm::event::{Event, KeyCode, KeyEventKind};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Layout, Rect},
+    style::Style,
+    text::{Line, Span},
+    widgets::{Block, Clear, List, ListItem, ListState, Paragraph, StatefulWidget, Widget},
+};
+use similar::TextDiff;
+use std::{fs, path::PathBuf};
+
+use crate::{
+    actions::Action,
+    keymap::{Context, Keymap},
+    markdown::CodeBlock,
+    theme::Theme,
+    utils::centered_rect,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum SaveStep {
+    /// choosing which code block to save
+    Pick,
+    /// typing the path of the file
+    Path,
+    /// waiting for the user to confirm, `diff` is set when the file already exists
+    Confirm { diff: Option<String> },
+}
+
+/// What the dialog wants the app to do after handling an event.
+#[derive(Debug, PartialEq)]
+pub enum SaveAction {
+    None,
+    Close,
+    Saved(PathBuf),
+    Failed(String),
+}
+
+/// A modal that saves one of the code blocks of a message to a file: the user picks the block,
+/// types the path (pre-filled from the file name hint of the block) and confirms after looking
+/// at the diff when the file already exists.
+#[derive(Debug)]
+pub struct SaveDialog<'a> {
+    pub blocks: Vec<CodeBlock>,
+    pub selected: usize,
+    pub step: SaveStep,
+    pub path: tui_textarea::TextArea<'a>,
+    /// vertical scroll of the diff
+    pub scroll: u16,
+    pub theme: Theme,
+}
+
+impl<'a> SaveDialog<'a> {
+    /// Returns None when there is no code block to save.
+    pub fn new(blocks: Vec<CodeBlock>) -> Option<Self> {
+        if blocks.is_empty() {
+            return None;
+        }
+
+        let mut dialog = Self {
+            blocks,
+            selected: 0,
+            step: SaveStep::Pick,
+            path: tui_textarea::TextArea::default(),
+            scroll: 0,
+            theme: Theme::default(),
+        };
+
+        if dialog.blocks.len() == 1 {
+            dialog.enter_path();
+        }
+
+        Some(dialog)
+    }
+
+    fn enter_path(&mut self) {
+        let filename = self.blocks[self.selected].filename.clone();
+        self.path = tui_textarea::TextArea::new(vec![filename.unwrap_or_default()]);
+        self.path.move_cursor(tui_textarea::CursorMove::End);
+        self.step = SaveStep::Path;
+    }
+
+    fn target(&self) -> PathBuf {
+        PathBuf::from(self.path.lines().join("").trim())
+    }
+
+    fn confirm(&mut self) {
+        let path = self.target();
+        let code = &self.blocks[self.selected].code;
+
+        let diff = fs::read_to_string(&path).ok().map(|current| {
+            TextDiff::from_lines(current.as_str(), code.as_str())
+                .unified_diff()
+                .header(&path.display().to_string(), "code block")
+                .to_string()
+        });
+
+        self.scroll = 0;
+        self.step = SaveStep::Confirm { diff };
+    }
+
+    fn save(&self) -> SaveAction {
+        let path = self.target();
+        let result = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, &self.blocks[self.selected].code));
+
+        match result {
+            Ok(()) => SaveAction::Saved(path),
+            Err(e) => SaveAction::Failed(format!("Failed to save {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn handle_events(&mut self, event: Event, keymap: &Keymap) -> SaveAction {
+        let Event::Key(key) = event else {
+            return SaveAction::None;
+        };
+
+        if key.kind != KeyEventKind::Press {
+            return SaveAction::None;
+        }
+
+        let action = keymap.action(Context::Popup, key);
+        if action == Some(Action::Unfocus) {
+            return SaveAction::Close;
+        }
+
+        match self.step {
+            SaveStep::Pick => match (action, key.code) {
+                (Some(Action::SelectNext), _) | (_, KeyCode::Char('j')) => {
+                    self.selected = (self.selected + 1).min(self.blocks.len() - 1)
+                }
+                (Some(Action::SelectPrev), _) | (_, KeyCode::Char('k')) => {
+                    self.selected = self.selected.saturating_sub(1)
+                }
+                (Some(Action::Submit), _) => self.enter_path(),
+                _ => {}
+            },
+            SaveStep::Path => match action {
+                Some(Action::Submit) if !self.target().as_os_str().is_empty() => self.confirm(),
+                Some(Action::Submit) => {}
+                _ => {
+                    self.path.input(key);
+                }
+            },
+            SaveStep::Confirm { .. } => match (action, key.code) {
+                (Some(Action::Submit), _) | (_, KeyCode::Char('y')) => return self.save(),
+                (_, KeyCode::Char('n')) => self.step = SaveStep::Path,
+                (Some(Action::SelectNext), _) | (_, KeyCode::Char('j')) => {
+                    self.scroll = self.scroll.saturating_add(1)
+                }
+                (Some(Action::SelectPrev), _) | (_, KeyCode::Char('k')) => {
+                    self.scroll = self.scroll.saturating_sub(1)
+                }
+                _ => {}
+            },
+        }
+
+        SaveAction::None
+    }
+
+    fn render_blocks(&self, area: Rect, buf: &mut Buffer) {
+        let items: Vec<ListItem> = self
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(i, block)| {
+                let first_line = block.code.lines().next().unwrap_or_default();
+                let name = block
+                    .filename
+                    .clone()
+                    .or_else(|| block.language.clone())
+                    .unwrap_or_else(|| String::from("text"));
+                ListItem::new(format!("{}. [{}] {}", i + 1, name, first_line))
+            })
+            .collect();
+
+        let list = List::new(items)
+            .block(Block::bordered().title("Save code block (j/k, Enter)"))
+            .highlight_style(self.theme.highlight);
+
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        StatefulWidget::render(list, area, buf, &mut state);
+    }
+
+    fn render_confirm(&self, diff: &Option<String>, area: Rect, buf: &mut Buffer) {
+        let path = self.target();
+
+        let Some(diff) = diff else {
+            Paragraph::new(format!("Create {}? (y/n)", path.display()))
+                .block(Block::bordered().title("Save code block"))
+                .render(area, buf);
+            return;
+        };
+
+        let lines: Vec<Line> = diff
+            .lines()
+            .map(|line| {
+                let style = match line.chars().next() {
+                    Some('+') => self.theme.success,
+                    Some('-') => self.theme.error,
+                    Some('@') => self.theme.accent,
+                    _ => Style::default(),
+                };
+                Line::from(Span::styled(line.to_string(), style))
+            })
+            .collect();
+
+        let title = if lines.is_empty() {
+            format!("{} is unchanged, overwrite? (y/n)", path.display())
+        } else {
+            format!("Overwrite {}? (y/n, j/k to scroll)", path.display())
+        };
+
+        Paragraph::new(lines)
+            .scroll((self.scroll, 0))
+            .block(Block::bordered().title(title))
+            .render(area, buf);
+    }
+}
+
+impl<'a> Widget for &mut SaveDialog<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let area = centered_rect(area, 80, 70);
+        Clear.render(area, buf);
+
+        match &self.step {
+            SaveStep::Pick => self.render_blocks(area, buf),
+            SaveStep::Path => {
+                let layout =
+                    Layout::vertical([Constraint::Length(3), Constraint::Min(0)]).split(area);
+
+                self.path.set_block(
+                    Block::bordered().title("Save to (Enter to confirm, Esc to cancel)"),
+                );
+                self.path.render(layout[0], buf);
+
+                Paragraph::new(self.blocks[self.selected].code.as_str())
+                    .block(Block::bordered().title("Preview"))
+                    .render(layout[1], buf);
+            }
+            SaveStep::Confirm { diff } => self.render_confirm(diff, area, buf),
+        }
+    }
+}
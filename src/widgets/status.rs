@@ -0,0 +1,235 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Alignment, Rect},
+    text::{Line, Span},
+    widgets::{Paragraph, Widget},
+};
+use std::{
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+use tokio::{task, time};
+
+use crate::{
+    client::Client,
+    conversation::Progress,
+    mcp::{Mcp, ServerStatus, ServerSummary},
+    theme::Theme,
+    widgets::chat::Chat,
+};
+
+/// how long a notification stays on screen
+pub const NOTIFICATION_DURATION: Duration = Duration::from_secs(3);
+
+/// how often the server is pinged
+pub const PING_INTERVAL: Duration = Duration::from_secs(5);
+
+/// the frames of the spinner shown while a reply is streaming
+const SPINNER: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// Whether the ollama server answers, as seen by the last ping.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Connection {
+    /// no ping has completed yet
+    #[default]
+    Unknown,
+    /// the server answered with its version
+    Connected(String),
+    /// the server could not be reached, with the reason
+    Disconnected(String),
+}
+
+/// Asks `host` for its version every `PING_INTERVAL`, until the receiver is dropped.
+pub fn ping(host: String) -> mpsc::Receiver<Connection> {
+    let (tx, rx) = mpsc::channel();
+
+    task::spawn(async move {
+        let client = Client::new(host).with_timeout(Duration::from_secs(2));
+        let mut interval = time::interval(PING_INTERVAL);
+
+        loop {
+            interval.tick().await;
+            let connection = match client.version().await {
+                Ok(version) => Connection::Connected(version),
+                Err(e) => Connection::Disconnected(e.to_string()),
+            };
+            if tx.send(connection).is_err() {
+                break;
+            }
+        }
+    });
+
+    rx
+}
+
+/// One line at the bottom of the screen: the progress of the streamed reply or a short lived
+/// notification, like "copied", on the left, the model and the state of the server on the
+/// right.
+#[derive(Debug, Default)]
+pub struct StatusLine {
+    notification: Option<(String, Instant)>,
+    pub model: String,
+    pub host: String,
+    pub connection: Connection,
+    pub progress: Option<Progress>,
+    /// the tokens per second of the last reply
+    pub last_rate: Option<f64>,
+    pings: Option<mpsc::Receiver<Connection>>,
+    /// the MCP servers as of the last tick, they start in the background
+    pub servers: Vec<ServerSummary>,
+    mcp: Mcp,
+    pub theme: Theme,
+}
+
+impl StatusLine {
+    pub fn notify(&mut self, text: impl Into<String>) {
+        self.notification = Some((text.into(), Instant::now()));
+    }
+
+    /// the current notification, if it has not expired yet
+    pub fn notification(&self) -> Option<&str> {
+        self.notification
+            .as_ref()
+            .filter(|(_, at)| at.elapsed() < NOTIFICATION_DURATION)
+            .map(|(text, _)| text.as_str())
+    }
+
+    /// Copies what is shown from the chat and starts pinging its server when it changed.
+    pub fn sync(&mut self, chat: &Chat) {
+        let conversation = &chat.conversation;
+        self.model = conversation.session.settings.model.clone();
+        self.progress = conversation.progress;
+        self.last_rate = conversation.last_rate;
+        self.theme = chat.theme;
+        self.mcp = conversation.mcp.clone();
+        self.servers = self.mcp.summaries();
+
+        let host = conversation.provider.host();
+        if self.host != host || self.pings.is_none() {
+            self.host = host.to_string();
+            self.connection = Connection::Unknown;
+            self.pings = Some(ping(self.host.clone()));
+        }
+    }
+
+    /// Reads the results of the pings and drops the expired notification, returns true when
+    /// the line has to be redrawn.
+    pub fn tick(&mut self) -> bool {
+        let mut changed = false;
+
+        if let Some(pings) = self.pings.as_ref() {
+            while let Ok(connection) = pings.try_recv() {
+                changed |= self.connection != connection;
+                self.connection = connection;
+            }
+        }
+
+        let servers = self.mcp.summaries();
+        if servers != self.servers {
+            self.servers = servers;
+            changed = true;
+        }
+
+        if self.notification.is_some() && self.notification().is_none() {
+            self.notification = None;
+            changed = true;
+        }
+
+        changed || self.progress.is_some()
+    }
+
+    fn left(&self) -> Line<'_> {
+        if let Some(text) = self.notification() {
+            return Line::styled(format!(" {}", text), self.theme.success);
+        }
+
+        if let Some(progress) = self.progress {
+            let elapsed = progress.elapsed();
+            let frame = SPINNER[(elapsed.as_millis() / 100) as usize % SPINNER.len()];
+            return Line::from(vec![
+                Span::styled(format!(" {} ", frame), self.theme.selected),
+                Span::raw(format!(
+                    "generating {:.1}s, {:.1} tok/s",
+                    elapsed.as_secs_f64(),
+                    progress.rate()
+                )),
+            ]);
+        }
+
+        match self.last_rate {
+            Some(rate) => Line::styled(format!(" last reply {:.1} tok/s", rate), self.theme.muted),
+            None => Line::default(),
+        }
+    }
+
+    fn right(&self) -> Line<'_> {
+        let (dot, state) = match &self.connection {
+            Connection::Unknown => (self.theme.muted, String::from("connecting")),
+            Connection::Connected(version) => (self.theme.success, format!("ollama {}", version)),
+            Connection::Disconnected(_) => (self.theme.error, String::from("offline")),
+        };
+        let host = self
+            .host
+            .trim_start_matches("http://")
+            .trim_start_matches("https://");
+
+        let mut spans = vec![
+            Span::styled(self.model.clone(), self.theme.accent),
+            Span::styled(format!("  {}  ", host), self.theme.muted),
+            Span::styled("● ", dot),
+            Span::raw(format!("{} ", state)),
+        ];
+        spans.extend(self.servers_state());
+        Line::from(spans)
+    }
+
+    /// how many MCP servers are ready, red when one of them failed, nothing without servers
+    fn servers_state(&self) -> Vec<Span<'_>> {
+        if self.servers.is_empty() {
+            return Vec::new();
+        }
+
+        let ready = self
+            .servers
+            .iter()
+            .filter(|server| server.status == ServerStatus::Ready)
+            .count();
+        let failed = self
+            .servers
+            .iter()
+            .any(|server| matches!(server.status, ServerStatus::Failed(_)));
+        let dot = match (failed, ready == self.servers.len()) {
+            (true, _) => self.theme.error,
+            (false, true) => self.theme.success,
+            (false, false) => self.theme.muted,
+        };
+
+        vec![
+            Span::styled(" ● ", dot),
+            Span::raw(format!("mcp {}/{} ", ready, self.servers.len())),
+        ]
+    }
+}
+
+impl Widget for &mut StatusLine {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let right = self.right();
+        let width = (right.width() as u16).min(area.width);
+
+        Paragraph::new(self.left()).render(
+            Rect {
+                width: area.width - width,
+                ..area
+            },
+            buf,
+        );
+        Paragraph::new(right).alignment(Alignment::Right).render(
+            Rect {
+                x: area.x + area.width - width,
+                width,
+                ..area
+            },
+            buf,
+        );
+    }
+}
@@ -1,11 +1,25 @@
+use ratatui::crossterm::event::KeyEvent;
 use ratatui::{
     buffer::Buffer,
     layout::Rect,
-    style::{Color, Style},
-    widgets::{Block, Widget},
+    text::{Line, Span},
+    widgets::{Block, Clear, List, ListItem, ListState, StatefulWidget, Widget},
 };
 use uuid::Uuid;
 
+use crate::{attachment::complete, commands, theme::Theme, widgets::vim::Vim};
+
+/// how many completions are shown at once
+pub const MAX_COMPLETIONS: usize = 8;
+
+/// An entry of the completion popup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    /// the text that replaces the word under the cursor
+    pub value: String,
+    pub description: String,
+}
+
 #[derive(Debug)]
 pub struct TextArea<'a> {
     pub id: Uuid,
@@ -15,6 +29,12 @@ pub struct TextArea<'a> {
     pub y: u16,
     pub height: u16,
     pub width: u16,
+    /// the slash commands or paths completing the word under the cursor
+    pub completions: Vec<Completion>,
+    pub completion_selected: usize,
+    /// modal editing, None when the textarea uses the default keybindings of tui_textarea
+    pub vim: Option<Vim>,
+    pub theme: Theme,
 }
 
 impl<'a> Default for TextArea<'a> {
@@ -27,6 +47,10 @@ impl<'a> Default for TextArea<'a> {
             y: 0,
             height: 0,
             width: 0,
+            completions: Vec::new(),
+            completion_selected: 0,
+            vim: None,
+            theme: Theme::default(),
         }
     }
 }
@@ -36,9 +60,152 @@ impl<'a> TextArea<'a> {
         let txt = self.area.lines().join("\n").trim().to_string();
         self.area = tui_textarea::TextArea::default();
         self.area.move_cursor(tui_textarea::CursorMove::Top);
+        self.completions.clear();
         txt
     }
 
+    pub fn with_vim(mut self, enabled: bool) -> Self {
+        self.vim = enabled.then(Vim::default);
+        self
+    }
+
+    pub fn input(&mut self, key: KeyEvent) {
+        match self.vim.as_mut() {
+            Some(vim) => vim.handle(key, &mut self.area),
+            None => {
+                self.area.input(key);
+            }
+        }
+        self.update_completions();
+    }
+
+    /// whether Esc is used by the textarea itself, like to leave the insert mode of vim
+    pub fn captures_esc(&self) -> bool {
+        self.vim.as_ref().is_some_and(|vim| vim.captures_esc())
+    }
+
+    pub fn text(&self) -> String {
+        self.area.lines().join("\n")
+    }
+
+    /// replaces the content of the textarea, leaving the cursor at the end
+    pub fn set_text(&mut self, text: &str) {
+        self.area = tui_textarea::TextArea::new(text.lines().map(String::from).collect());
+        self.area.move_cursor(tui_textarea::CursorMove::Bottom);
+        self.area.move_cursor(tui_textarea::CursorMove::End);
+        self.completions.clear();
+    }
+
+    /// the word right before the cursor
+    fn word_at_cursor(&self) -> Option<String> {
+        let (row, col) = self.area.cursor();
+        let line = self.area.lines().get(row)?;
+        let before: String = line.chars().take(col).collect();
+        before.rsplit(char::is_whitespace).next().map(String::from)
+    }
+
+    /// The text before the cursor that a completion replaces and the completions for it: the
+    /// name of a slash command at the start of the input, or the path of an `@path` reference.
+    fn completion_candidates(&self) -> Option<(String, Vec<Completion>)> {
+        let word = self.word_at_cursor()?;
+
+        let is_command = self.area.cursor().0 == 0
+            && word.len() == self.area.cursor().1
+            && word.starts_with('/')
+            && !word.starts_with("//");
+        if is_command {
+            let completions = commands::complete(&word[1..])
+                .into_iter()
+                .map(|command| Completion {
+                    value: format!("/{} ", command.name),
+                    description: format!("{} - {}", command.usage, command.description),
+                })
+                .collect();
+            return Some((word, completions));
+        }
+
+        let prefix = word.strip_prefix('@')?.to_string();
+        let completions = complete(&prefix)
+            .into_iter()
+            .filter(|c| *c != prefix)
+            .map(|value| Completion {
+                value,
+                description: String::new(),
+            })
+            .collect();
+        Some((prefix, completions))
+    }
+
+    pub fn update_completions(&mut self) {
+        self.completion_selected = 0;
+        self.completions = self
+            .completion_candidates()
+            .map(|(_, completions)| completions)
+            .unwrap_or_default();
+    }
+
+    pub fn select_completion(&mut self, forward: bool) {
+        let len = self.completions.len();
+        if len == 0 {
+            return;
+        }
+
+        self.completion_selected = if forward {
+            (self.completion_selected + 1) % len
+        } else {
+            (self.completion_selected + len - 1) % len
+        };
+    }
+
+    /// replaces the word under the cursor with the selected completion
+    pub fn accept_completion(&mut self) {
+        let (Some((prefix, _)), Some(completion)) = (
+            self.completion_candidates(),
+            self.completions.get(self.completion_selected).cloned(),
+        ) else {
+            return;
+        };
+
+        for _ in 0..prefix.chars().count() {
+            self.area.delete_char();
+        }
+        self.area.insert_str(completion.value);
+        self.update_completions();
+    }
+
+    /// renders the completion popup right above `area`, the area of the textarea
+    pub fn render_completions(&self, area: Rect, buf: &mut Buffer) {
+        if self.completions.is_empty() {
+            return;
+        }
+
+        let height = (self.completions.len().min(MAX_COMPLETIONS) + 2) as u16;
+        let popup = Rect {
+            x: area.x,
+            y: area.y.saturating_sub(height),
+            width: area.width.min(80),
+            height: height.min(area.y),
+        };
+
+        let items: Vec<ListItem> = self
+            .completions
+            .iter()
+            .map(|c| {
+                ListItem::new(Line::from(vec![
+                    Span::raw(c.value.clone()),
+                    Span::styled(c.description.clone(), self.theme.muted),
+                ]))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title("Tab to complete"))
+            .highlight_style(self.theme.highlight);
+
+        Clear.render(popup, buf);
+        let mut state = ListState::default().with_selected(Some(self.completion_selected));
+        StatefulWidget::render(list, popup, buf, &mut state);
+    }
+
     pub fn is_within(&self, x: u16, y: u16) -> bool {
         let within_x = x >= self.x && x < self.x + self.width;
         let within_y = y >= self.y && y < self.y + self.height;
@@ -49,9 +216,9 @@ impl<'a> TextArea<'a> {
 impl<'a> Widget for &mut TextArea<'a> {
     fn render(self, area: Rect, buf: &mut Buffer) {
         let selected_style = if self.is_selected {
-            Style::default().fg(Color::LightGreen)
+            self.theme.selected
         } else {
-            Style::default().fg(Color::White)
+            self.theme.border
         };
 
         self.x = area.x;
@@ -59,7 +226,12 @@ impl<'a> Widget for &mut TextArea<'a> {
         self.height = area.height;
         self.width = area.width;
 
-        self.area.set_block(Block::bordered().style(selected_style));
+        let mut block = Block::bordered().style(selected_style);
+        if let Some(vim) = self.vim.as_ref() {
+            block = block.title(format!(" {} ", vim.status()));
+        }
+
+        self.area.set_block(block);
         self.area.render(area, buf);
     }
 }
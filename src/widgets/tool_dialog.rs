@@ -0,0 +1,116 @@
+use ratatui::crossterm::event::{Event, KeyCode, KeyEventKind};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::Modifier,
+    text::{Line, Span},
+    widgets::{Block, Clear, Paragraph, Widget, Wrap},
+};
+use serde_json::Value;
+
+use crate::{
+    actions::Action,
+    client::ToolCall,
+    keymap::{Context, Keymap},
+    theme::Theme,
+    utils::centered_rect,
+};
+
+/// The answer of the user to a `ToolDialog`.
+#[derive(Debug, PartialEq)]
+pub enum ToolAnswer {
+    None,
+    /// runs this call
+    Allow,
+    /// runs this call and the next calls of the tool without asking
+    AlwaysAllow,
+    Refuse,
+}
+
+/// A modal showing a call the model wants to make, with its exact arguments, until the user
+/// allows or refuses it.
+#[derive(Debug)]
+pub struct ToolDialog {
+    pub call: ToolCall,
+    pub theme: Theme,
+}
+
+impl ToolDialog {
+    pub fn new(call: ToolCall) -> Self {
+        Self {
+            call,
+            theme: Theme::default(),
+        }
+    }
+
+    pub fn handle_events(&self, event: Event, keymap: &Keymap) -> ToolAnswer {
+        let Event::Key(key) = event else {
+            return ToolAnswer::None;
+        };
+
+        if key.kind != KeyEventKind::Press {
+            return ToolAnswer::None;
+        }
+
+        match (keymap.action(Context::Popup, key), key.code) {
+            (Some(Action::Submit), _) | (_, KeyCode::Char('y')) => ToolAnswer::Allow,
+            (_, KeyCode::Char('a')) => ToolAnswer::AlwaysAllow,
+            (Some(Action::Unfocus), _) | (_, KeyCode::Char('n')) => ToolAnswer::Refuse,
+            _ => ToolAnswer::None,
+        }
+    }
+
+    /// the arguments one per line, the strings without quotes so commands read as typed
+    fn arguments(&self) -> Vec<Line<'static>> {
+        let Value::Object(arguments) = &self.call.function.arguments else {
+            return vec![Line::raw(self.call.function.arguments.to_string())];
+        };
+
+        let mut lines = Vec::new();
+        for (name, value) in arguments {
+            let value = match value {
+                Value::String(text) => text.clone(),
+                value => value.to_string(),
+            };
+            let mut values = value.lines();
+            lines.push(Line::from(vec![
+                Span::styled(format!("{}: ", name), self.theme.muted),
+                Span::styled(
+                    values.next().unwrap_or_default().to_string(),
+                    self.theme.accent,
+                ),
+            ]));
+            for line in values {
+                lines.push(Line::styled(format!("  {}", line), self.theme.accent));
+            }
+        }
+        lines
+    }
+}
+
+impl Widget for &ToolDialog {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let area = centered_rect(area, 70, 50);
+        Clear.render(area, buf);
+
+        let name = &self.call.function.name;
+        let mut lines = vec![
+            Line::from(vec![
+                Span::raw("The model wants to run "),
+                Span::styled(name.clone(), self.theme.accent.add_modifier(Modifier::BOLD)),
+            ]),
+            Line::default(),
+        ];
+        lines.extend(self.arguments());
+        lines.push(Line::default());
+        lines.push(Line::styled(
+            format!("y: run  a: always run {}  n: refuse", name),
+            self.theme.muted,
+        ));
+
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(Block::bordered().title(format!("Run {}? (y/a/n)", name)))
+            .render(area, buf);
+    }
+}
@@ -0,0 +1,547 @@
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::{collections::HashMap, fmt};
+use tui_textarea::{CursorMove, TextArea};
+
+/// the register used when none is chosen with `"x`
+pub const UNNAMED_REGISTER: char = '"';
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Mode {
+    #[default]
+    Normal,
+    Insert,
+    Visual,
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mode::Normal => write!(f, "NORMAL"),
+            Mode::Insert => write!(f, "INSERT"),
+            Mode::Visual => write!(f, "VISUAL"),
+        }
+    }
+}
+
+/// Text stored by a yank or a delete, `linewise` when whole lines were taken (dd, yy, dj...)
+/// so pasting it creates new lines instead of inserting it in the middle of one.
+#[derive(Debug, Clone, Default)]
+pub struct Register {
+    pub text: String,
+    pub linewise: bool,
+}
+
+/// a position in the textarea, (row, column) in characters
+type Pos = (usize, usize);
+
+/// Modal editing on top of `tui_textarea::TextArea`, with the normal, insert and visual modes,
+/// the usual motions (h j k l w b e 0 $ gg G), the d, c and y operators with text objects
+/// (iw aw i" a" i( a( ...), undo/redo and registers.
+#[derive(Debug, Default)]
+pub struct Vim {
+    pub mode: Mode,
+    /// the keys of a command that is not complete yet, ex: "d" or "ci"
+    pending: String,
+    /// the register chosen with `"x` for the next command
+    register: Option<char>,
+    registers: HashMap<char, Register>,
+}
+
+impl Vim {
+    /// whether Esc is used by the editor, in which case it should not close the textarea
+    pub fn captures_esc(&self) -> bool {
+        self.mode != Mode::Normal || !self.pending.is_empty() || self.register.is_some()
+    }
+
+    pub fn register(&self, name: char) -> Option<&Register> {
+        self.registers.get(&name)
+    }
+
+    /// the text shown in the border of the textarea, ex: "NORMAL d"
+    pub fn status(&self) -> String {
+        let register = self
+            .register
+            .map(|r| format!("\"{}", r))
+            .unwrap_or_default();
+        format!("{} {}{}", self.mode, register, self.pending)
+            .trim_end()
+            .to_string()
+    }
+
+    pub fn handle(&mut self, key: KeyEvent, area: &mut TextArea) {
+        match self.mode {
+            Mode::Insert => {
+                if key.code == KeyCode::Esc {
+                    self.mode = Mode::Normal;
+                    if area.cursor().1 > 0 {
+                        area.move_cursor(CursorMove::Back);
+                    }
+                } else {
+                    area.input(key);
+                }
+            }
+            Mode::Normal | Mode::Visual => self.handle_command(key, area),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.pending.clear();
+        self.register = None;
+    }
+
+    fn handle_command(&mut self, key: KeyEvent, area: &mut TextArea) {
+        if key.code == KeyCode::Esc {
+            self.reset();
+            if self.mode == Mode::Visual {
+                area.cancel_selection();
+                self.mode = Mode::Normal;
+            }
+            return;
+        }
+
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            if key.code == KeyCode::Char('r') {
+                area.redo();
+            }
+            self.reset();
+            return;
+        }
+
+        let c = match key.code {
+            KeyCode::Char(c) => c,
+            KeyCode::Left => 'h',
+            KeyCode::Down => 'j',
+            KeyCode::Up => 'k',
+            KeyCode::Right => 'l',
+            KeyCode::Home => '0',
+            KeyCode::End => '$',
+            _ => return,
+        };
+
+        if self.pending == "\"" {
+            self.register = Some(c);
+            self.pending.clear();
+            return;
+        }
+
+        self.pending.push(c);
+        let keys = self.pending.clone();
+
+        let done = match self.mode {
+            Mode::Visual => self.visual(&keys, area),
+            _ => self.normal(&keys, area),
+        };
+
+        if done {
+            self.reset();
+        }
+    }
+
+    /// Runs the normal mode command `keys`, returns false when more keys are needed.
+    fn normal(&mut self, keys: &str, area: &mut TextArea) -> bool {
+        if let Some(m) = motion(keys) {
+            move_cursor(area, m);
+            return true;
+        }
+
+        let (row, col) = area.cursor();
+        let line_len = line_len(area, row);
+
+        match keys {
+            "\"" | "g" | "d" | "c" | "y" => return false,
+            "i" => self.mode = Mode::Insert,
+            "a" => {
+                if col < line_len {
+                    area.move_cursor(CursorMove::Forward);
+                }
+                self.mode = Mode::Insert;
+            }
+            "I" => {
+                area.move_cursor(CursorMove::Head);
+                self.mode = Mode::Insert;
+            }
+            "A" => {
+                area.move_cursor(CursorMove::End);
+                self.mode = Mode::Insert;
+            }
+            "o" => {
+                area.move_cursor(CursorMove::End);
+                area.insert_newline();
+                self.mode = Mode::Insert;
+            }
+            "O" => {
+                area.move_cursor(CursorMove::Head);
+                area.insert_newline();
+                area.move_cursor(CursorMove::Up);
+                self.mode = Mode::Insert;
+            }
+            "v" => {
+                area.start_selection();
+                self.mode = Mode::Visual;
+            }
+            "x" if col < line_len => self.apply('d', (row, col), (row, col + 1), false, area),
+            "X" if col > 0 => self.apply('d', (row, col - 1), (row, col), false, area),
+            "D" => self.apply('d', (row, col), (row, line_len), false, area),
+            "C" => self.apply('c', (row, col), (row, line_len), false, area),
+            "p" => self.paste(true, area),
+            "P" => self.paste(false, area),
+            "u" => {
+                area.undo();
+            }
+            "dd" | "cc" | "yy" => self.apply_lines(keys.chars().next().unwrap(), row, row, area),
+            _ => return self.operator(keys, area),
+        }
+
+        true
+    }
+
+    /// Runs an operator followed by a motion or a text object, ex: "dw", "ci\"" or "yG".
+    fn operator(&mut self, keys: &str, area: &mut TextArea) -> bool {
+        let mut chars = keys.chars();
+        let Some(op @ ('d' | 'c' | 'y')) = chars.next() else {
+            return true;
+        };
+        let target: String = chars.collect();
+
+        if target == "g" || target == "i" || target == "a" {
+            return false;
+        }
+
+        let start = area.cursor();
+
+        // like vim, cw on a word changes up to the end of the word and keeps the space after it
+        let under_cursor = area.lines()[start.0].chars().nth(start.1);
+        if op == 'c'
+            && target == "w"
+            && under_cursor.is_some_and(|c| class(c) != 0)
+            && let Some((_, end)) = word_object(area.lines(), start, true)
+        {
+            self.apply(op, start, end, false, area);
+            return true;
+        }
+
+        if let Some(m) = motion(&target) {
+            match m {
+                CursorMove::Up | CursorMove::Down | CursorMove::Top | CursorMove::Bottom => {
+                    move_cursor(area, m);
+                    let end = area.cursor();
+                    jump(area, start);
+                    self.apply_lines(op, start.0.min(end.0), start.0.max(end.0), area);
+                }
+                _ => {
+                    move_cursor(area, m);
+                    let mut end = area.cursor();
+                    if m == CursorMove::WordEnd {
+                        end.1 = (end.1 + 1).min(line_len(area, end.0));
+                    }
+                    self.apply(op, start.min(end), start.max(end), false, area);
+                }
+            }
+            return true;
+        }
+
+        let mut target = target.chars();
+        if let (Some(kind @ ('i' | 'a')), Some(object)) = (target.next(), target.next())
+            && let Some((from, to)) = text_object(area.lines(), start, kind == 'i', object)
+        {
+            self.apply(op, from, to, false, area);
+        }
+
+        true
+    }
+
+    /// Runs the visual mode command `keys`, returns false when more keys are needed.
+    fn visual(&mut self, keys: &str, area: &mut TextArea) -> bool {
+        if let Some(m) = motion(keys) {
+            move_cursor(area, m);
+            return true;
+        }
+
+        let op = match keys {
+            "\"" | "g" => return false,
+            "v" => {
+                area.cancel_selection();
+                self.mode = Mode::Normal;
+                return true;
+            }
+            "d" | "x" => 'd',
+            "c" | "s" => 'c',
+            "y" => 'y',
+            _ => return true,
+        };
+
+        let Some((start, end)) = area.selection_range() else {
+            self.mode = Mode::Normal;
+            return true;
+        };
+
+        // the selection of vim includes the character under the cursor
+        let end = (end.0, (end.1 + 1).min(line_len(area, end.0)));
+        self.mode = Mode::Normal;
+        self.apply(op, start, end, false, area);
+        true
+    }
+
+    /// Applies the operator `op` to the text between `start` and `end` (exclusive).
+    fn apply(&mut self, op: char, start: Pos, end: Pos, linewise: bool, area: &mut TextArea) {
+        area.cancel_selection();
+        jump(area, start);
+        area.start_selection();
+        jump(area, end);
+
+        if op == 'y' {
+            area.copy();
+            jump(area, start);
+        } else {
+            area.cut();
+        }
+
+        self.store(area.yank_text(), linewise);
+
+        if op == 'c' {
+            self.mode = Mode::Insert;
+        }
+    }
+
+    /// Applies the operator `op` to the lines from `first` to `last` (inclusive).
+    fn apply_lines(&mut self, op: char, first: usize, last: usize, area: &mut TextArea) {
+        let text = area.lines()[first..=last].join("\n");
+        let line_count = area.lines().len();
+        let last_len = line_len(area, last);
+
+        match op {
+            'y' => {
+                self.store(text, true);
+                return;
+            }
+            'c' => self.apply('c', (first, 0), (last, last_len), true, area),
+            _ if last + 1 < line_count => self.apply('d', (first, 0), (last + 1, 0), true, area),
+            _ if first > 0 => {
+                let previous_len = line_len(area, first - 1);
+                self.apply('d', (first - 1, previous_len), (last, last_len), true, area);
+                area.move_cursor(CursorMove::Head);
+            }
+            _ => self.apply('d', (0, 0), (last, last_len), true, area),
+        }
+
+        self.store(text, true);
+    }
+
+    /// saves `text` in the unnamed register and in the register chosen with `"x`, if any
+    fn store(&mut self, text: String, linewise: bool) {
+        let register = Register { text, linewise };
+        if let Some(name) = self.register.filter(|r| *r != UNNAMED_REGISTER) {
+            self.registers.insert(name, register.clone());
+        }
+        self.registers.insert(UNNAMED_REGISTER, register);
+    }
+
+    /// pastes the chosen register after the cursor (p) or before it (P)
+    fn paste(&mut self, after: bool, area: &mut TextArea) {
+        let name = self.register.unwrap_or(UNNAMED_REGISTER);
+        let Some(register) = self.registers.get(&name).cloned() else {
+            return;
+        };
+
+        let (row, col) = area.cursor();
+
+        if register.linewise {
+            if after {
+                area.move_cursor(CursorMove::End);
+                area.insert_newline();
+                area.insert_str(&register.text);
+                jump(area, (row + 1, 0));
+            } else {
+                area.move_cursor(CursorMove::Head);
+                area.insert_str(&register.text);
+                area.insert_newline();
+                jump(area, (row, 0));
+            }
+            return;
+        }
+
+        if after && col < line_len(area, row) {
+            area.move_cursor(CursorMove::Forward);
+        }
+        area.insert_str(&register.text);
+    }
+}
+
+fn motion(keys: &str) -> Option<CursorMove> {
+    Some(match keys {
+        "h" => CursorMove::Back,
+        "j" => CursorMove::Down,
+        "k" => CursorMove::Up,
+        "l" => CursorMove::Forward,
+        "w" => CursorMove::WordForward,
+        "b" => CursorMove::WordBack,
+        "e" => CursorMove::WordEnd,
+        "0" => CursorMove::Head,
+        "$" => CursorMove::End,
+        "gg" => CursorMove::Top,
+        "G" => CursorMove::Bottom,
+        _ => return None,
+    })
+}
+
+/// moves the cursor like `TextArea::move_cursor`, except h and l do not wrap to other lines
+fn move_cursor(area: &mut TextArea, m: CursorMove) {
+    let (row, col) = area.cursor();
+    match m {
+        CursorMove::Back if col == 0 => {}
+        CursorMove::Forward if col >= line_len(area, row) => {}
+        CursorMove::Top => jump(area, (0, 0)),
+        CursorMove::Bottom => jump(area, (area.lines().len() - 1, 0)),
+        _ => area.move_cursor(m),
+    }
+}
+
+fn jump(area: &mut TextArea, (row, col): Pos) {
+    area.move_cursor(CursorMove::Jump(row as u16, col as u16));
+}
+
+fn line_len(area: &TextArea, row: usize) -> usize {
+    area.lines().get(row).map_or(0, |l| l.chars().count())
+}
+
+/// 0 for whitespace, 1 for word characters and 2 for punctuation, a word is a run of
+/// characters of the same class
+fn class(c: char) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if c.is_alphanumeric() || c == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+/// The range (end exclusive) of the text object `object` around `cursor`: w for words, quotes
+/// and brackets (b for parentheses and B for braces). `inner` excludes the delimiters.
+fn text_object(lines: &[String], (row, col): Pos, inner: bool, object: char) -> Option<(Pos, Pos)> {
+    // the cursor is past the end of the line after $, it is on the last character in vim
+    let len = lines.get(row).map_or(0, |line| line.chars().count());
+    let cursor = (row, col.min(len.saturating_sub(1)));
+    match object {
+        'w' => word_object(lines, cursor, inner),
+        '"' | '\'' | '`' => quote_object(lines, cursor, inner, object),
+        '(' | ')' | 'b' => bracket_object(lines, cursor, inner, ('(', ')')),
+        '[' | ']' => bracket_object(lines, cursor, inner, ('[', ']')),
+        '{' | '}' | 'B' => bracket_object(lines, cursor, inner, ('{', '}')),
+        '<' | '>' => bracket_object(lines, cursor, inner, ('<', '>')),
+        _ => None,
+    }
+}
+
+fn word_object(lines: &[String], (row, col): Pos, inner: bool) -> Option<(Pos, Pos)> {
+    let line: Vec<char> = lines.get(row)?.chars().collect();
+    let current = class(*line.get(col)?);
+
+    let mut start = col;
+    while start > 0 && class(line[start - 1]) == current {
+        start -= 1;
+    }
+    let mut end = col + 1;
+    while end < line.len() && class(line[end]) == current {
+        end += 1;
+    }
+
+    if !inner {
+        if end < line.len() && class(line[end]) == 0 {
+            while end < line.len() && class(line[end]) == 0 {
+                end += 1;
+            }
+        } else {
+            while start > 0 && class(line[start - 1]) == 0 {
+                start -= 1;
+            }
+        }
+    }
+
+    Some(((row, start), (row, end)))
+}
+
+fn quote_object(lines: &[String], (row, col): Pos, inner: bool, quote: char) -> Option<(Pos, Pos)> {
+    let quotes: Vec<usize> = lines
+        .get(row)?
+        .chars()
+        .enumerate()
+        .filter(|(_, c)| *c == quote)
+        .map(|(i, _)| i)
+        .collect();
+
+    let (open, close) = quotes
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .find(|(_, close)| *close >= col)?;
+
+    if inner {
+        Some(((row, open + 1), (row, close)))
+    } else {
+        Some(((row, open), (row, close + 1)))
+    }
+}
+
+fn bracket_object(
+    lines: &[String],
+    cursor: Pos,
+    inner: bool,
+    (open, close): (char, char),
+) -> Option<(Pos, Pos)> {
+    let chars: Vec<(Pos, char)> = lines
+        .iter()
+        .enumerate()
+        .flat_map(|(row, line)| {
+            line.chars()
+                .enumerate()
+                .map(move |(col, c)| ((row, col), c))
+        })
+        .collect();
+
+    // the brackets are looked for from the character under the cursor backwards, there is no
+    // such character on an empty line or past the end of one
+    let before = chars.iter().take_while(|(pos, _)| *pos < cursor).count();
+    let current = chars
+        .get(before)
+        .is_some_and(|(pos, _)| *pos == cursor)
+        .then_some(before);
+    let last = current.map_or(before, |i| i + 1);
+
+    let mut depth = 0;
+    let mut start = None;
+    for i in (0..last).rev() {
+        let c = chars[i].1;
+        if c == close && Some(i) != current {
+            depth += 1;
+        } else if c == open {
+            if depth == 0 {
+                start = Some(i);
+                break;
+            }
+            depth -= 1;
+        }
+    }
+    let start = start?;
+
+    let mut depth = 0;
+    let mut end = None;
+    for (i, (_, c)) in chars.iter().enumerate().skip(start + 1) {
+        if *c == open {
+            depth += 1;
+        } else if *c == close {
+            if depth == 0 {
+                end = Some(i);
+                break;
+            }
+            depth -= 1;
+        }
+    }
+    let end = end?;
+
+    let ((start_row, start_col), (end_row, end_col)) = (chars[start].0, chars[end].0);
+    if inner {
+        Some(((start_row, start_col + 1), (end_row, end_col)))
+    } else {
+        Some(((start_row, start_col), (end_row, end_col + 1)))
+    }
+}
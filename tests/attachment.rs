@@ -0,0 +1,21 @@
+use ink::attachment::resolve;
+use std::{fs, process::Command};
+use uuid::Uuid;
+
+#[cfg(unix)]
+#[test]
+fn refuses_devices_and_fifos() {
+    let attachments = resolve("read @/dev/zero");
+    assert_eq!(attachments.len(), 1);
+    assert_eq!(attachments[0].error.as_deref(), Some("not a regular file"));
+
+    // a FIFO without a writer would block the read forever
+    let fifo = std::env::temp_dir().join(format!("ink-fifo-{}", Uuid::new_v4()));
+    let created = Command::new("mkfifo").arg(&fifo).status().unwrap();
+    assert!(created.success());
+
+    let attachments = resolve(&format!("read @{}", fifo.display()));
+    assert_eq!(attachments.len(), 1);
+    assert_eq!(attachments[0].error.as_deref(), Some("not a regular file"));
+    fs::remove_file(fifo).unwrap();
+}
@@ -0,0 +1,210 @@
+mod support;
+
+use futures_util::StreamExt;
+use ink::{
+    client::{self, ChatEvent, ChatRequest, Client, Error, GenerateRequest},
+    conversation::Role,
+};
+use std::time::Duration;
+use support::{MODELS, MockOllama, Reply, VERSION};
+
+/// reads the whole stream, stopping at the first error
+async fn collect(mut events: client::ChatStream) -> (Vec<ChatEvent>, Option<Error>) {
+    let mut collected = Vec::new();
+    while let Some(event) = events.next().await {
+        match event {
+            Ok(event) => collected.push(event),
+            Err(e) => return (collected, Some(e)),
+        }
+    }
+    (collected, None)
+}
+
+fn deltas(events: &[ChatEvent]) -> Vec<&str> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            ChatEvent::Delta(text) => Some(text.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn streams_the_reply_token_by_token() {
+    let server = MockOllama::start(vec![Reply::tokens(&["The", " sky", " is", " blue"])]).await;
+    let client = Client::new(&server.host);
+
+    let request = ChatRequest::new("llama3.2").with_message(Role::User, "Why is the sky blue?");
+    let (events, error) = collect(client.chat(&request).await.unwrap()).await;
+
+    assert!(error.is_none());
+    assert_eq!(deltas(&events), ["The", " sky", " is", " blue"]);
+    let Some(ChatEvent::Done(stats)) = events.last() else {
+        panic!("the stream does not end with Done: {:?}", events);
+    };
+    assert_eq!(stats.eval_count, Some(4));
+    assert_eq!(stats.rate(), Some(2.0));
+}
+
+#[tokio::test]
+async fn sends_the_history_and_the_options() {
+    let server = MockOllama::start(vec![]).await;
+    let client = Client::new(&server.host);
+
+    let request = ChatRequest::new("qwen2.5-coder:7b")
+        .with_system("Be brief.")
+        .with_message(Role::User, "hi")
+        .with_message(Role::Assistant, "hello")
+        .with_message(Role::User, "how are you?")
+        .with_temperature(Some(0.5));
+    collect(client.chat(&request).await.unwrap()).await;
+
+    let requests = server.requests_to("/api/chat");
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        requests[0].json(),
+        serde_json::json!({
+            "model": "qwen2.5-coder:7b",
+            "messages": [
+                { "role": "system", "content": "Be brief." },
+                { "role": "user", "content": "hi" },
+                { "role": "assistant", "content": "hello" },
+                { "role": "user", "content": "how are you?" },
+            ],
+            "options": { "temperature": 0.5 },
+            "stream": true,
+        })
+    );
+}
+
+#[tokio::test]
+async fn leaves_the_temperature_out_by_default() {
+    let server = MockOllama::start(vec![]).await;
+    let client = Client::new(&server.host);
+
+    collect(client.chat(&ChatRequest::new("llama3.2")).await.unwrap()).await;
+
+    assert_eq!(
+        server.requests()[0].json()["options"],
+        serde_json::json!({})
+    );
+}
+
+#[tokio::test]
+async fn reports_the_error_of_ollama() {
+    let server = MockOllama::start(vec![Reply::error(
+        404,
+        "model \"nope\" not found, try pulling it first",
+    )])
+    .await;
+    let client = Client::new(&server.host);
+
+    let Err(error) = client.chat(&ChatRequest::new("nope")).await else {
+        panic!("the request succeeded");
+    };
+
+    let Error::Status(status, message) = &error else {
+        panic!("unexpected error: {:?}", error);
+    };
+    assert_eq!(status.as_u16(), 404);
+    assert_eq!(message, "model \"nope\" not found, try pulling it first");
+    assert_eq!(
+        error.to_string(),
+        "404 Not Found: model \"nope\" not found, try pulling it first"
+    );
+}
+
+#[tokio::test]
+async fn reports_a_disconnect_after_the_received_tokens() {
+    let server = MockOllama::start(vec![Reply::disconnect(&["Once", " upon"])]).await;
+    let client = Client::new(&server.host);
+
+    let (events, error) = collect(client.chat(&ChatRequest::new("llama3.2")).await.unwrap()).await;
+
+    assert_eq!(deltas(&events), ["Once", " upon"]);
+    assert!(
+        matches!(error, Some(Error::Interrupted(_))),
+        "unexpected error: {:?}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn reports_a_reply_closed_without_its_last_line() {
+    let server = MockOllama::start(vec![Reply::truncate(&["Once", " upon"])]).await;
+    let client = Client::new(&server.host);
+
+    let (events, error) = collect(client.chat(&ChatRequest::new("llama3.2")).await.unwrap()).await;
+
+    assert_eq!(deltas(&events), ["Once", " upon"]);
+    assert!(
+        !events
+            .iter()
+            .any(|event| matches!(event, ChatEvent::Done(_)))
+    );
+    assert!(
+        matches!(error, Some(Error::Interrupted(_))),
+        "unexpected error: {:?}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn fails_when_the_server_is_down() {
+    let server = MockOllama::start(vec![]).await;
+    let host = server.host.clone();
+    drop(server);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let result = Client::new(host).chat(&ChatRequest::new("llama3.2")).await;
+
+    assert!(matches!(result, Err(Error::Http(_))));
+}
+
+#[tokio::test]
+async fn gives_up_after_the_timeout() {
+    let server = MockOllama::start(vec![Reply::slow(&["zzz"], Duration::from_secs(5))]).await;
+    let client = Client::new(&server.host).with_timeout(Duration::from_millis(200));
+
+    let (events, error) = match client.chat(&ChatRequest::new("llama3.2")).await {
+        Ok(events) => collect(events).await,
+        Err(e) => (Vec::new(), Some(e)),
+    };
+
+    assert!(deltas(&events).is_empty());
+    assert!(error.is_some());
+}
+
+#[tokio::test]
+async fn streams_generated_text() {
+    let server = MockOllama::start(vec![Reply::tokens(&["4"])]).await;
+    let client = Client::new(&server.host);
+
+    let request = GenerateRequest::new("llama3.2", "2 + 2 =").with_system("Only digits.");
+    let (events, error) = collect(client.generate(&request).await.unwrap()).await;
+
+    assert!(error.is_none());
+    assert_eq!(deltas(&events), ["4"]);
+    let body = server.requests_to("/api/generate")[0].json();
+    assert_eq!(body["prompt"], "2 + 2 =");
+    assert_eq!(body["system"], "Only digits.");
+}
+
+#[tokio::test]
+async fn lists_the_models() {
+    let server = MockOllama::start(vec![]).await;
+    let client = Client::new(&server.host);
+
+    let models = client.models().await.unwrap();
+
+    let names: Vec<&str> = models.iter().map(|model| model.name.as_str()).collect();
+    assert_eq!(names, MODELS);
+}
+
+#[tokio::test]
+async fn reads_the_version() {
+    let server = MockOllama::start(vec![]).await;
+
+    assert_eq!(Client::new(&server.host).version().await.unwrap(), VERSION);
+}
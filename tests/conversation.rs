@@ -0,0 +1,466 @@
+mod support;
+
+use ink::tools::Policy;
+use ink::{
+    client::Client,
+    conversation::{Conversation, ConversationEvent, MAX_TOOL_ROUNDS, Role},
+    session::Session,
+};
+use std::{path::PathBuf, sync::Arc, time::Duration};
+use support::{MockOllama, Reply};
+use tokio::time::timeout;
+use uuid::Uuid;
+
+/// a conversation asking `server`, saved in the temporary directory
+fn conversation(server: &MockOllama) -> Conversation {
+    let mut session = Session::new();
+    session.path = Some(session_path());
+    Conversation::new(session).with_provider(Arc::new(Client::new(&server.host)))
+}
+
+fn session_path() -> PathBuf {
+    std::env::temp_dir().join(format!("ink-test-{}.json", Uuid::new_v4()))
+}
+
+/// receives until no reply is streaming, fails after a few seconds
+async fn finish(conversation: &mut Conversation) {
+    timeout(Duration::from_secs(5), async {
+        while conversation.generating_id.is_some() {
+            conversation.receive().await;
+        }
+    })
+    .await
+    .expect("the reply did not finish");
+}
+
+/// receives until a call waits for a confirmation, fails after a few seconds
+async fn until_confirmation(conversation: &mut Conversation) {
+    timeout(Duration::from_secs(5), async {
+        while conversation.pending_call.is_none() {
+            conversation.receive().await;
+        }
+    })
+    .await
+    .expect("no call waits for a confirmation");
+}
+
+fn text(conversation: &Conversation, id: Uuid) -> String {
+    conversation.session.tree.get(&id).unwrap().text.clone()
+}
+
+#[tokio::test]
+async fn writes_the_reply_into_a_new_message() {
+    let server = MockOllama::start(vec![Reply::tokens(&["Hi", " there"])]).await;
+    let mut conversation = conversation(&server);
+
+    let prompt = conversation.submit(String::from("hello"), None);
+    let reply = conversation.generating_id.unwrap();
+    finish(&mut conversation).await;
+
+    assert_eq!(text(&conversation, reply), "Hi there");
+    let msg = conversation.session.tree.get(&reply).unwrap();
+    assert_eq!(msg.role, Role::Assistant);
+    assert_eq!(msg.parent, Some(prompt));
+    assert!(!msg.generating);
+    assert_eq!(
+        conversation.take_events(),
+        [
+            ConversationEvent::Submitted(prompt),
+            ConversationEvent::ReplyDone(reply)
+        ]
+    );
+    assert_eq!(conversation.last_rate, Some(2.0));
+
+    let path = conversation.session.path();
+    assert!(path.exists(), "the session was not saved");
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn shows_the_tokens_as_they_arrive() {
+    let delay = Duration::from_millis(200);
+    let server = MockOllama::start(vec![Reply::slow(&["one", " two", " three"], delay)]).await;
+    let mut conversation = conversation(&server);
+
+    conversation.submit(String::from("count"), None);
+    let reply = conversation.generating_id.unwrap();
+
+    conversation.receive().await;
+    assert_eq!(text(&conversation, reply), "one");
+    assert_eq!(conversation.progress.unwrap().tokens, 1);
+
+    finish(&mut conversation).await;
+    assert_eq!(text(&conversation, reply), "one two three");
+    assert!(conversation.progress.is_none());
+    std::fs::remove_file(conversation.session.path()).unwrap();
+}
+
+#[tokio::test]
+async fn sends_the_system_prompt_and_the_history() {
+    let server = MockOllama::start(vec![Reply::tokens(&["4"]), Reply::tokens(&["8"])]).await;
+    let mut conversation = conversation(&server);
+    conversation.session.settings.system = Some(String::from("Only digits."));
+    conversation.session.settings.temperature = Some(0.0);
+
+    conversation.submit(String::from("2 + 2"), None);
+    finish(&mut conversation).await;
+    conversation.submit(String::from("times 2"), None);
+    finish(&mut conversation).await;
+
+    let requests = server.requests_to("/api/chat");
+    assert_eq!(requests.len(), 2);
+    let body = requests[1].json();
+    assert_eq!(body["model"], conversation.session.settings.model.as_str());
+    assert_eq!(body["options"]["temperature"], 0.0);
+    assert_eq!(
+        body["messages"],
+        serde_json::json!([
+            { "role": "system", "content": "Only digits." },
+            { "role": "user", "content": "2 + 2" },
+            { "role": "assistant", "content": "4" },
+            { "role": "user", "content": "times 2" },
+        ])
+    );
+    std::fs::remove_file(conversation.session.path()).unwrap();
+}
+
+#[tokio::test]
+async fn turns_a_failed_request_into_an_error_message() {
+    let server = MockOllama::start(vec![Reply::error(404, "model not found")]).await;
+    let mut conversation = conversation(&server);
+
+    conversation.submit(String::from("hello"), None);
+    let reply = conversation.generating_id.unwrap();
+    finish(&mut conversation).await;
+
+    let msg = conversation.session.tree.get(&reply).unwrap();
+    assert_eq!(msg.role, Role::Error);
+    assert_eq!(msg.text, "404 Not Found: model not found");
+    assert!(
+        conversation
+            .take_events()
+            .contains(&ConversationEvent::ReplyFailed(
+                reply,
+                String::from("404 Not Found: model not found")
+            ))
+    );
+    assert_eq!(conversation.last_rate, None);
+    std::fs::remove_file(conversation.session.path()).unwrap();
+}
+
+#[tokio::test]
+async fn keeps_the_tokens_received_before_a_disconnect() {
+    let server = MockOllama::start(vec![Reply::disconnect(&["Once", " upon"])]).await;
+    let mut conversation = conversation(&server);
+
+    conversation.submit(String::from("tell me a story"), None);
+    let reply = conversation.generating_id.unwrap();
+    finish(&mut conversation).await;
+
+    let msg = conversation.session.tree.get(&reply).unwrap();
+    assert_eq!(msg.role, Role::Error);
+    assert!(
+        msg.text
+            .starts_with("Once upon\n\nThe reply was interrupted"),
+        "unexpected text: {:?}",
+        msg.text
+    );
+    std::fs::remove_file(conversation.session.path()).unwrap();
+}
+
+#[tokio::test]
+async fn never_sends_error_messages_back() {
+    let server = MockOllama::start(vec![Reply::error(500, "out of memory")]).await;
+    let mut conversation = conversation(&server);
+
+    conversation.submit(String::from("hello"), None);
+    let failed = conversation.generating_id.unwrap();
+    finish(&mut conversation).await;
+    assert!(conversation.regenerate(&failed, None).is_some());
+    finish(&mut conversation).await;
+
+    let requests = server.requests_to("/api/chat");
+    assert_eq!(requests.len(), 2);
+    assert_eq!(
+        requests[1].json()["messages"],
+        serde_json::json!([{ "role": "user", "content": "hello" }])
+    );
+    std::fs::remove_file(conversation.session.path()).unwrap();
+}
+
+#[tokio::test]
+async fn never_continues_a_reply_outside_of_the_active_path() {
+    let server = MockOllama::start(vec![]).await;
+    let mut conversation = conversation(&server);
+
+    conversation.submit(String::from("hello"), None);
+    let first = conversation.generating_id.unwrap();
+    finish(&mut conversation).await;
+    conversation.regenerate(&first, None).unwrap();
+    finish(&mut conversation).await;
+
+    assert!(!conversation.continue_message(&first));
+    assert!(!conversation.session.tree.get(&first).unwrap().generating);
+    assert!(conversation.generating_id.is_none());
+    std::fs::remove_file(conversation.session.path()).unwrap();
+}
+
+#[tokio::test]
+async fn cancels_the_reply_when_the_session_changes() {
+    let server = MockOllama::start(vec![Reply::slow(&["slow"], Duration::from_secs(5))]).await;
+    let mut conversation = conversation(&server);
+
+    conversation.submit(String::from("hello"), None);
+    conversation.set_session(Session::new());
+
+    assert!(conversation.generating_id.is_none());
+    assert!(
+        timeout(Duration::from_millis(200), conversation.receive())
+            .await
+            .is_err(),
+        "a cancelled reply was received"
+    );
+}
+
+/// a directory with a file for the tools, set as the root of the sandbox of `conversation`
+fn workspace(conversation: &mut Conversation) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ink-tools-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("notes.txt"), "hello from the file").unwrap();
+    conversation.session.settings.tools = true;
+    conversation.sandbox.root = dir.canonicalize().unwrap();
+    dir
+}
+
+/// the JSON lines of the audit log of `conversation`
+fn audit(conversation: &Conversation) -> Vec<serde_json::Value> {
+    let path = ink::tools::audit_path(&conversation.session.path());
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+fn remove(conversation: &Conversation, workspace: PathBuf) {
+    let path = conversation.session.path();
+    let _ = std::fs::remove_file(ink::tools::audit_path(&path));
+    let _ = std::fs::remove_file(path);
+    std::fs::remove_dir_all(workspace).unwrap();
+}
+
+#[tokio::test]
+async fn runs_the_tools_the_model_calls_and_sends_back_their_results() {
+    let server = MockOllama::start(vec![
+        Reply::tool_call("read_file", serde_json::json!({ "path": "notes.txt" })),
+        Reply::tokens(&["It says hello"]),
+    ])
+    .await;
+    let mut conversation = conversation(&server);
+    let workspace = workspace(&mut conversation);
+
+    conversation.submit(String::from("what is in the file?"), None);
+    let call = conversation.generating_id.unwrap();
+    finish(&mut conversation).await;
+
+    let path = conversation.session.tree.active_path();
+    assert_eq!(path.len(), 4);
+    assert_eq!(path[1], call);
+    let result = conversation.session.tree.get(&path[2]).unwrap();
+    assert_eq!(result.role, Role::Tool);
+    assert_eq!(result.tool_name.as_deref(), Some("read_file"));
+    assert_eq!(result.text, "hello from the file");
+    assert_eq!(text(&conversation, path[3]), "It says hello");
+    assert!(
+        conversation
+            .take_events()
+            .contains(&ConversationEvent::ToolCalled(path[2]))
+    );
+
+    let requests = server.requests_to("/api/chat");
+    assert_eq!(requests.len(), 2);
+    let body = requests[1].json();
+    assert_eq!(
+        body["tools"].as_array().unwrap().len(),
+        ink::tools::TOOLS.len()
+    );
+    assert_eq!(
+        body["messages"][1]["tool_calls"][0]["function"]["name"],
+        "read_file"
+    );
+    assert_eq!(
+        body["messages"][2],
+        serde_json::json!({
+            "role": "tool",
+            "content": "hello from the file",
+            "tool_name": "read_file",
+        })
+    );
+    remove(&conversation, workspace);
+}
+
+#[tokio::test]
+async fn keeps_the_file_tools_in_the_working_directory() {
+    let server = MockOllama::start(vec![
+        Reply::tool_call(
+            "read_file",
+            serde_json::json!({ "path": "../../etc/passwd" }),
+        ),
+        Reply::tokens(&["I cannot"]),
+    ])
+    .await;
+    let mut conversation = conversation(&server);
+    let workspace = workspace(&mut conversation);
+
+    conversation.submit(String::from("read the passwords"), None);
+    finish(&mut conversation).await;
+
+    let result = conversation.session.tree.active_path()[2];
+    assert!(
+        text(&conversation, result).starts_with("Error: ../../etc/passwd is outside of"),
+        "unexpected result: {}",
+        text(&conversation, result)
+    );
+    remove(&conversation, workspace);
+}
+
+#[tokio::test]
+async fn waits_for_a_confirmation_before_running_a_command() {
+    let server = MockOllama::start(vec![
+        Reply::tool_call("run_shell", serde_json::json!({ "command": "echo hi" })),
+        Reply::tokens(&["Done"]),
+        Reply::tool_call("run_shell", serde_json::json!({ "command": "rm -rf ." })),
+        Reply::tokens(&["Ok, I won't"]),
+    ])
+    .await;
+    let mut conversation = conversation(&server);
+    let workspace = workspace(&mut conversation);
+
+    conversation.submit(String::from("say hi"), None);
+    until_confirmation(&mut conversation).await;
+    let result = conversation.session.tree.active_path()[2];
+    assert_eq!(
+        conversation.take_events().last(),
+        Some(&ConversationEvent::ConfirmTool(result))
+    );
+    let (id, call) = conversation.pending_call.clone().unwrap();
+    assert_eq!(id, result);
+    assert_eq!(call.function.arguments["command"], "echo hi");
+    // nothing runs until the user answers
+    let _ = timeout(Duration::from_millis(200), conversation.receive()).await;
+    assert!(conversation.pending_call.is_some());
+    assert_eq!(text(&conversation, result), "");
+
+    assert!(conversation.confirm_tool(true));
+    finish(&mut conversation).await;
+    assert_eq!(text(&conversation, result), "hi\n");
+
+    conversation.submit(String::from("now delete everything"), None);
+    until_confirmation(&mut conversation).await;
+    assert!(conversation.confirm_tool(false));
+    finish(&mut conversation).await;
+    assert!(workspace.join("notes.txt").exists());
+    let refused = conversation.session.tree.active_path()[6];
+    assert_eq!(
+        text(&conversation, refused),
+        "Error: the user refused to run run_shell"
+    );
+
+    let audit = audit(&conversation);
+    assert_eq!(audit.len(), 2);
+    assert_eq!(audit[0]["tool"], "run_shell");
+    assert_eq!(audit[0]["arguments"]["command"], "echo hi");
+    assert_eq!(audit[0]["decision"], "confirmed");
+    assert!(audit[0]["duration_ms"].is_u64());
+    assert_eq!(audit[0]["output_bytes"], 3);
+    assert_eq!(audit[1]["decision"], "refused");
+    assert!(audit[1].get("duration_ms").is_none());
+    remove(&conversation, workspace);
+}
+
+#[tokio::test]
+async fn never_runs_a_denied_tool() {
+    let server = MockOllama::start(vec![
+        Reply::tool_call("list_directory", serde_json::json!({})),
+        Reply::tokens(&["I am not allowed"]),
+    ])
+    .await;
+    let mut conversation = conversation(&server);
+    let workspace = workspace(&mut conversation);
+    conversation
+        .sandbox
+        .policies
+        .insert(String::from("list_directory"), Policy::Deny);
+
+    conversation.submit(String::from("what is there?"), None);
+    finish(&mut conversation).await;
+
+    let result = conversation.session.tree.active_path()[2];
+    assert_eq!(
+        text(&conversation, result),
+        "Error: the tool list_directory is not allowed"
+    );
+    assert_eq!(audit(&conversation)[0]["decision"], "denied");
+    remove(&conversation, workspace);
+}
+
+#[tokio::test]
+async fn makes_the_model_answer_after_too_many_tool_calls() {
+    let mut script: Vec<Reply> = (0..MAX_TOOL_ROUNDS)
+        .map(|_| Reply::tool_call("read_file", serde_json::json!({ "path": "missing" })))
+        .collect();
+    script.push(Reply::tokens(&["I give up"]));
+    let server = MockOllama::start(script).await;
+    let mut conversation = conversation(&server);
+    let workspace = workspace(&mut conversation);
+
+    conversation.submit(String::from("read it"), None);
+    finish(&mut conversation).await;
+
+    let requests = server.requests_to("/api/chat");
+    assert_eq!(requests.len(), MAX_TOOL_ROUNDS + 1);
+    assert!(requests[MAX_TOOL_ROUNDS - 1].json()["tools"].is_array());
+    assert!(requests[MAX_TOOL_ROUNDS].json().get("tools").is_none());
+    let last = conversation.session.tree.last().unwrap();
+    assert_eq!(text(&conversation, last), "I give up");
+    remove(&conversation, workspace);
+}
+
+#[tokio::test]
+async fn asks_once_more_when_the_model_calls_tools_past_the_limit() {
+    let mut script: Vec<Reply> = (0..=MAX_TOOL_ROUNDS)
+        .map(|_| Reply::tool_call("read_file", serde_json::json!({ "path": "missing" })))
+        .collect();
+    script.push(Reply::tokens(&["Fine"]));
+    let server = MockOllama::start(script).await;
+    let mut conversation = conversation(&server);
+    let workspace = workspace(&mut conversation);
+
+    conversation.submit(String::from("read it"), None);
+    finish(&mut conversation).await;
+
+    let requests = server.requests_to("/api/chat");
+    assert_eq!(requests.len(), MAX_TOOL_ROUNDS + 2);
+    assert!(requests[MAX_TOOL_ROUNDS + 1].json().get("tools").is_none());
+    assert_eq!(audit(&conversation).len(), MAX_TOOL_ROUNDS);
+    let last = conversation.session.tree.last().unwrap();
+    assert_eq!(text(&conversation, last), "Fine");
+    remove(&conversation, workspace);
+}
+
+#[tokio::test]
+async fn never_offers_the_tools_unless_enabled() {
+    let server = MockOllama::start(vec![Reply::tokens(&["Hi"])]).await;
+    let mut conversation = conversation(&server);
+
+    conversation.submit(String::from("hello"), None);
+    finish(&mut conversation).await;
+
+    assert!(
+        server.requests_to("/api/chat")[0]
+            .json()
+            .get("tools")
+            .is_none()
+    );
+    std::fs::remove_file(conversation.session.path()).unwrap();
+}
@@ -0,0 +1,34 @@
+use ink::history::{History, MAX_HISTORY};
+use std::fs;
+use uuid::Uuid;
+
+fn lines(path: &std::path::Path) -> Vec<String> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn trims_the_file_once_it_holds_twice_the_limit() {
+    let path = std::env::temp_dir().join(format!("ink-history-{}", Uuid::new_v4()));
+    let mut history = History::load(path.clone());
+    let mut other = History::load(path.clone());
+
+    for i in 0..2 * MAX_HISTORY {
+        history.push(&format!("prompt {}", i)).unwrap();
+    }
+    assert_eq!(lines(&path).len(), 2 * MAX_HISTORY);
+
+    // another ink appends to the same file, its prompt survives the trim
+    other.push("from the other ink").unwrap();
+    history.push("last").unwrap();
+
+    let kept = lines(&path);
+    assert_eq!(kept.len(), MAX_HISTORY);
+    assert_eq!(kept[MAX_HISTORY - 2], "from the other ink");
+    assert_eq!(kept[MAX_HISTORY - 1], "last");
+    assert_eq!(history.entries, kept);
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,138 @@
+use ink::{
+    actions::Action,
+    config::Config,
+    keymap::{Context, Key, Keymap},
+};
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+fn key(code: KeyCode, modifiers: KeyModifiers) -> Key {
+    Key::new(code, modifiers)
+}
+
+/// the keymap of the `[keys]` sections of `toml`
+fn keymap(toml: &str) -> Result<Keymap, String> {
+    Keymap::from_config(&Config::parse(toml).unwrap().keys)
+}
+
+#[test]
+fn parses_keys_with_their_modifiers() {
+    assert_eq!("q".parse(), Ok(key(KeyCode::Char('q'), KeyModifiers::NONE)));
+    assert_eq!(
+        "Ctrl+P".parse(),
+        Ok(key(KeyCode::Char('p'), KeyModifiers::CONTROL))
+    );
+    assert_eq!(
+        "ctrl+alt+x".parse(),
+        Ok(key(
+            KeyCode::Char('x'),
+            KeyModifiers::CONTROL | KeyModifiers::ALT
+        ))
+    );
+    // shift is part of the character
+    assert_eq!("Shift+y".parse::<Key>(), "Y".parse());
+    assert_eq!(
+        "Shift+Tab".parse(),
+        Ok(key(KeyCode::BackTab, KeyModifiers::NONE))
+    );
+    assert_eq!(
+        "Ctrl++".parse(),
+        Ok(key(KeyCode::Char('+'), KeyModifiers::CONTROL))
+    );
+    assert_eq!(
+        "Space".parse(),
+        Ok(key(KeyCode::Char(' '), KeyModifiers::NONE))
+    );
+    assert_eq!(
+        "PageDown".parse(),
+        Ok(key(KeyCode::PageDown, KeyModifiers::NONE))
+    );
+}
+
+#[test]
+fn parses_the_function_keys() {
+    assert_eq!("F1".parse(), Ok(key(KeyCode::F(1), KeyModifiers::NONE)));
+    assert_eq!(
+        "Alt+f12".parse(),
+        Ok(key(KeyCode::F(12), KeyModifiers::ALT))
+    );
+    assert_eq!("Ctrl+F5".parse::<Key>().unwrap().to_string(), "Ctrl+F5");
+}
+
+#[test]
+fn rejects_invalid_keys() {
+    assert_eq!(
+        "Hyper+x".parse::<Key>(),
+        Err(String::from("Invalid modifier \"Hyper\" in \"Hyper+x\""))
+    );
+    assert_eq!(
+        "Ctrl+Nope".parse::<Key>(),
+        Err(String::from("Invalid key \"Ctrl+Nope\""))
+    );
+    assert!("Fx".parse::<Key>().is_err());
+    assert!("F999".parse::<Key>().is_err());
+}
+
+#[test]
+fn replaces_the_default_keys_of_an_action() {
+    let keymap = keymap("[keys.global]\nquit = [\"Ctrl+Q\", \"x\"]\nhelp = []").unwrap();
+
+    let quit = |code, modifiers| {
+        keymap.action(Context::Global, KeyEvent::new(code, modifiers)) == Some(Action::Quit)
+    };
+    assert!(quit(KeyCode::Char('q'), KeyModifiers::CONTROL));
+    assert!(quit(KeyCode::Char('x'), KeyModifiers::NONE));
+    assert!(!quit(KeyCode::Char('q'), KeyModifiers::NONE));
+    assert!(keymap.keys(Context::Global, Action::Help).is_empty());
+    // the other actions keep their keys
+    assert_eq!(
+        keymap.keys(Context::Global, Action::ScrollDown),
+        ["j".parse().unwrap()]
+    );
+}
+
+#[test]
+fn falls_back_to_the_global_keys_in_a_message() {
+    let keymap = Keymap::default();
+    let press =
+        |context, c| keymap.action(context, KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+
+    assert_eq!(press(Context::Message, 'r'), Some(Action::Regenerate));
+    assert_eq!(press(Context::Message, 'q'), Some(Action::Quit));
+    assert_eq!(press(Context::Global, 'r'), None);
+    // the input and the popups do not fall back, their keys insert text
+    assert_eq!(press(Context::Input, 'q'), None);
+    assert_eq!(press(Context::Popup, 'q'), None);
+}
+
+#[test]
+fn fails_on_unknown_contexts_and_actions() {
+    assert_eq!(
+        keymap("[keys.sidebar]\nquit = \"x\"").unwrap_err(),
+        "Unknown keybinding context \"sidebar\""
+    );
+    assert_eq!(
+        keymap("[keys.global]\nfly = \"x\"").unwrap_err(),
+        "Unknown action \"fly\""
+    );
+    assert_eq!(
+        keymap("[keys.global]\nquit = \"Ctrl+Nope\"").unwrap_err(),
+        "Invalid key \"Ctrl+Nope\""
+    );
+}
+
+#[test]
+fn reports_the_keys_bound_to_two_actions() {
+    assert!(Keymap::default().conflicts().is_empty());
+
+    assert_eq!(
+        keymap("[keys.global]\nquit = \"j\"").unwrap_err(),
+        "Conflicting keybindings: j is bound to both scroll_down and quit in global"
+    );
+    // a message key hides the global key of another action
+    assert_eq!(
+        keymap("[keys.message]\nregenerate = \"q\"").unwrap_err(),
+        "Conflicting keybindings: q is bound to regenerate in message and to quit in global"
+    );
+    // the same key in two contexts that do not fall back to each other is fine
+    assert!(keymap("[keys.input]\nhelp = \"F2\"\n[keys.popup]\nsubmit = \"F2\"").is_ok());
+}
@@ -0,0 +1,20 @@
+use ink::markdown::code_blocks;
+
+#[test]
+fn closes_a_block_on_a_fence_at_least_as_long_as_the_opening_one() {
+    let blocks = code_blocks("```rust\nfn main() {}\n````\nafter\n~~~~\n~~~\n```\n~~~~~\n");
+
+    assert_eq!(blocks.len(), 2);
+    assert_eq!(blocks[0].language.as_deref(), Some("rust"));
+    assert_eq!(blocks[0].code, "fn main() {}\n");
+    // a shorter fence or one of the other character is part of the code
+    assert_eq!(blocks[1].code, "~~~\n```\n");
+}
+
+#[test]
+fn keeps_a_fence_with_an_info_string_in_the_block() {
+    let blocks = code_blocks("````markdown\n```rust\nlet x = 1;\n```\n````");
+
+    assert_eq!(blocks.len(), 1);
+    assert_eq!(blocks[0].code, "```rust\nlet x = 1;\n```\n");
+}
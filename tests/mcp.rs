@@ -0,0 +1,208 @@
+mod support;
+
+use ink::{
+    client::Client,
+    conversation::{Conversation, Role},
+    mcp::{Mcp, McpClient, McpServerConfig, ServerStatus},
+    session::Session,
+    tools::Policy,
+};
+use serde_json::json;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use support::{MockOllama, Reply};
+use tokio::time::{sleep, timeout};
+use uuid::Uuid;
+
+/// the stub server of `src/bin/mcp_stub.rs`, started with `args`
+fn stub(args: &[&str]) -> McpServerConfig {
+    McpServerConfig {
+        command: env!("CARGO_BIN_EXE_mcp_stub").to_string(),
+        args: args.iter().map(|arg| arg.to_string()).collect(),
+        env: HashMap::new(),
+        policy: Some(Policy::Allow),
+    }
+}
+
+/// starts the servers of `configs` and waits until none of them is starting
+async fn start(configs: &[(&str, McpServerConfig)]) -> Mcp {
+    let configs: HashMap<String, McpServerConfig> = configs
+        .iter()
+        .map(|(name, config)| (name.to_string(), config.clone()))
+        .collect();
+    let mcp = Mcp::start(&configs).unwrap();
+
+    timeout(Duration::from_secs(5), async {
+        while mcp
+            .summaries()
+            .iter()
+            .any(|server| server.status == ServerStatus::Starting)
+        {
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("the servers did not start");
+    mcp
+}
+
+#[test]
+fn rejects_server_names_with_the_separator() {
+    let configs = HashMap::from([(String::from("my__server"), stub(&[]))]);
+
+    assert_eq!(
+        Mcp::start(&configs).unwrap_err(),
+        "Invalid MCP server name my__server, it cannot contain \"__\""
+    );
+}
+
+#[tokio::test]
+async fn matches_the_ids_sent_back_as_strings() {
+    let client = McpClient::connect("stub", &stub(&["--string-ids"]))
+        .await
+        .unwrap();
+
+    let text = timeout(
+        Duration::from_secs(5),
+        client.call_tool("echo", json!({ "text": "hi" })),
+    )
+    .await
+    .expect("the response was dropped");
+    assert_eq!(text, Ok(String::from("hi")));
+}
+
+#[tokio::test]
+async fn lists_what_the_server_offers() {
+    let client = McpClient::connect("stub", &stub(&[])).await.unwrap();
+
+    let tools: Vec<&str> = client.tools.iter().map(|tool| tool.name.as_str()).collect();
+    assert_eq!(tools, ["echo", "add", "fail"]);
+    assert_eq!(client.tools[0].input_schema["required"], json!(["text"]));
+    // the resources come in two pages
+    let resources: Vec<&str> = client.resources.iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(resources, ["readme", "changelog"]);
+    assert_eq!(client.prompts[0].name, "review");
+    assert!(client.is_alive());
+}
+
+#[tokio::test]
+async fn only_lists_what_the_server_is_capable_of() {
+    let client = McpClient::connect("stub", &stub(&["--no-prompts"]))
+        .await
+        .unwrap();
+
+    assert!(client.prompts.is_empty());
+    assert_eq!(client.tools.len(), 3);
+}
+
+#[tokio::test]
+async fn calls_the_tools_of_the_server() {
+    let client = McpClient::connect("stub", &stub(&[])).await.unwrap();
+
+    assert_eq!(
+        client.call_tool("echo", json!({ "text": "hello" })).await,
+        Ok(String::from("hello"))
+    );
+    assert_eq!(
+        client.call_tool("add", json!({ "a": 2, "b": 3 })).await,
+        Ok(String::from("5"))
+    );
+    assert_eq!(
+        client.call_tool("fail", json!({})).await,
+        Err(String::from("the tool failed on purpose"))
+    );
+    assert_eq!(
+        client.call_tool("nope", json!({})).await,
+        Err(String::from("stub failed: Unknown tool: \"nope\""))
+    );
+}
+
+#[tokio::test]
+async fn fails_the_requests_of_a_server_that_exited() {
+    // the handshake takes 5 requests, the server exits on the next one
+    let client = McpClient::connect("stub", &stub(&["--exit-after", "5"]))
+        .await
+        .unwrap();
+
+    let result = client.call_tool("echo", json!({ "text": "hello" })).await;
+    assert_eq!(
+        result,
+        Err(String::from("stub exited before answering tools/call"))
+    );
+    sleep(Duration::from_millis(100)).await;
+    assert!(!client.is_alive());
+}
+
+#[tokio::test]
+async fn shows_the_state_of_every_server() {
+    let missing = McpServerConfig {
+        command: String::from("ink-no-such-command"),
+        ..stub(&[])
+    };
+    let mcp = start(&[("stub", stub(&[])), ("broken", missing)]).await;
+
+    let servers = mcp.summaries();
+    assert_eq!(servers.len(), 2);
+    assert_eq!(servers[0].name, "broken");
+    assert!(
+        matches!(&servers[0].status, ServerStatus::Failed(e) if e.starts_with("Failed to start")),
+        "{:?}",
+        servers[0].status
+    );
+    assert_eq!(
+        servers[1].to_string(),
+        "stub: 3 tools, 2 resources, 1 prompts"
+    );
+
+    let names: Vec<String> = mcp
+        .specs()
+        .into_iter()
+        .map(|spec| spec.function.name)
+        .collect();
+    assert_eq!(names, ["stub__echo", "stub__add", "stub__fail"]);
+    assert!(mcp.find("stub__echo").is_some());
+    assert!(mcp.find("broken__echo").is_none());
+    assert!(mcp.find("echo").is_none());
+}
+
+#[tokio::test]
+async fn offers_the_tools_of_the_servers_to_the_model() {
+    let server = MockOllama::start(vec![
+        Reply::tool_call("stub__echo", json!({ "text": "from the stub" })),
+        Reply::tokens(&["The stub says hi"]),
+    ])
+    .await;
+    let mut session = Session::new();
+    session.path = Some(std::env::temp_dir().join(format!("ink-test-{}.json", Uuid::new_v4())));
+    session.settings.tools = true;
+    let mut conversation =
+        Conversation::new(session).with_provider(Arc::new(Client::new(&server.host)));
+    conversation.mcp = start(&[("stub", stub(&[]))]).await;
+
+    conversation.submit(String::from("call the stub"), None);
+    timeout(Duration::from_secs(5), async {
+        while conversation.generating_id.is_some() {
+            conversation.receive().await;
+        }
+    })
+    .await
+    .expect("the reply did not finish");
+
+    let path = conversation.session.tree.active_path();
+    let result = conversation.session.tree.get(&path[2]).unwrap();
+    assert_eq!(result.role, Role::Tool);
+    assert_eq!(result.text, "from the stub");
+
+    let requests = server.requests_to("/api/chat");
+    let tools: Vec<String> = requests[0].json()["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tool| tool["function"]["name"].as_str().unwrap().to_string())
+        .collect();
+    assert!(tools.iter().any(|name| name == "read_file"));
+    assert!(tools.iter().any(|name| name == "stub__echo"));
+
+    let path = conversation.session.path();
+    let _ = std::fs::remove_file(ink::tools::audit_path(&path));
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,41 @@
+use ink::{
+    conversation::{ChatMessage, Role},
+    session::Session,
+};
+use std::fs;
+use uuid::Uuid;
+
+#[test]
+fn loads_a_session_with_missing_messages_up_to_the_damage() {
+    let path = std::env::temp_dir().join(format!("ink-test-{}.json", Uuid::new_v4()));
+    let mut session = Session::new();
+    session.path = Some(path.clone());
+    let prompt = session
+        .tree
+        .push(None, ChatMessage::new(Role::User, String::from("hello")));
+    let reply = session.tree.push(
+        Some(prompt),
+        ChatMessage::new(Role::Assistant, String::from("hi")),
+    );
+    let next = session.tree.push(
+        Some(reply),
+        ChatMessage::new(Role::User, String::from("bye")),
+    );
+    session.save().unwrap();
+
+    // the reply is lost, the prompt still points to it and the next prompt still follows it
+    let mut json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    json["tree"]["nodes"]
+        .as_object_mut()
+        .unwrap()
+        .remove(&reply.to_string());
+    fs::write(&path, json.to_string()).unwrap();
+
+    let session = Session::load(&path).unwrap();
+    assert_eq!(session.tree.active_path(), [prompt]);
+    assert_eq!(session.tree.active_messages().len(), 1);
+    assert_eq!(session.tree.path_to(&next), [next]);
+    assert_eq!(session.tree.siblings(&next), (0, 0));
+    fs::remove_file(path).unwrap();
+}
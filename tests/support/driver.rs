@@ -0,0 +1,279 @@
+//! Drives `App` without a terminal: synthetic key and mouse events go through
+//! `App::handle_event` like in the event loop, every step is drawn on a `TestBackend` and the
+//! screen is compared with the snapshot files of `tests/snapshots`.
+//!
+//! A missing snapshot fails the test, `UPDATE_SNAPSHOTS=1` writes the missing ones and rewrites
+//! the ones that changed. The status line shows timings and the state of the server, so it is left out of
+//! the snapshots.
+
+use futures_util::{FutureExt, future::BoxFuture, stream};
+use ink::{
+    app::App,
+    client::{self, ChatEvent, ChatRequest, ChatStream, Provider, Stats, ToolCall},
+    conversation::{ChatMessage, Role},
+    keymap::Keymap,
+    session::Session,
+    tools,
+    widgets::chat::Chat,
+};
+use ratatui::{
+    Terminal,
+    backend::TestBackend,
+    crossterm::event::{
+        Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    },
+};
+use std::{
+    env, fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+type Sender = mpsc::UnboundedSender<client::Result<ChatEvent>>;
+
+/// A provider whose replies are written by the test, one event at a time.
+#[derive(Debug, Clone, Default)]
+pub struct ManualProvider {
+    /// one sender per request, the last one streams the current reply
+    replies: Arc<Mutex<Vec<Sender>>>,
+    requests: Arc<Mutex<Vec<ChatRequest>>>,
+}
+
+impl ManualProvider {
+    fn current(&self) -> Sender {
+        self.replies
+            .lock()
+            .unwrap()
+            .last()
+            .cloned()
+            .expect("no request was sent")
+    }
+
+    pub fn requests(&self) -> Vec<ChatRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Provider for ManualProvider {
+    fn host(&self) -> &str {
+        // nothing listens there, the pings of the status line fail right away
+        "http://127.0.0.1:9"
+    }
+
+    fn send(&self, request: ChatRequest) -> BoxFuture<'static, client::Result<ChatStream>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.replies.lock().unwrap().push(tx);
+        self.requests.lock().unwrap().push(request);
+
+        let events = stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|event| (event, rx))
+        });
+        async move { Ok(Box::pin(events) as ChatStream) }.boxed()
+    }
+}
+
+/// The app with a chat of `messages`, drawn on a terminal of `width` x `height`.
+pub struct Driver {
+    pub app: App<'static>,
+    pub provider: ManualProvider,
+    term: Terminal<TestBackend>,
+}
+
+impl Driver {
+    pub fn new(width: u16, height: u16, messages: &[(Role, &str)]) -> Self {
+        let mut session = Session::new();
+        // the replies save the session, never next to the real ones
+        session.path = Some(env::temp_dir().join(format!("ink-snapshot-{}.json", Uuid::new_v4())));
+
+        let mut parent = None;
+        for (role, text) in messages {
+            parent = Some(
+                session
+                    .tree
+                    .push(parent, ChatMessage::new(*role, text.to_string())),
+            );
+        }
+
+        let provider = ManualProvider::default();
+        let chat = Chat::from_session(session)
+            .with_provider(provider.clone())
+            .with_user_name("you");
+
+        let mut driver = Self {
+            app: App::new(chat, Keymap::default()),
+            provider,
+            term: Terminal::new(TestBackend::new(width, height)).unwrap(),
+        };
+        driver.draw();
+        driver
+    }
+
+    /// draws a frame like the event loop does after every change
+    pub fn draw(&mut self) {
+        // the headers show the time of the messages, the snapshots must not depend on the clock
+        for msg in self.app.chat.conversation.session.tree.iter_mut() {
+            msg.created = None;
+        }
+        self.app.draw(&mut self.term).unwrap();
+    }
+
+    /// handles `event` then draws, like the event loop
+    pub fn event(&mut self, event: Event) {
+        self.app.handle_event(event).unwrap();
+        self.app.chat.receive_pending();
+        self.app.show_notifications();
+        self.draw();
+    }
+
+    pub fn key(&mut self, code: KeyCode) {
+        self.event(Event::Key(KeyEvent::new(code, KeyModifiers::NONE)));
+    }
+
+    pub fn key_with(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        self.event(Event::Key(KeyEvent::new(code, modifiers)));
+    }
+
+    /// types `text` one key at a time
+    pub fn type_text(&mut self, text: &str) {
+        for c in text.chars() {
+            self.key(KeyCode::Char(c));
+        }
+    }
+
+    fn mouse(&mut self, kind: MouseEventKind, column: u16, row: u16) {
+        self.event(Event::Mouse(MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        }));
+    }
+
+    pub fn click(&mut self, column: u16, row: u16) {
+        self.mouse(MouseEventKind::Down(MouseButton::Left), column, row);
+    }
+
+    /// clicks on the textarea, the app starts with nothing focused
+    pub fn focus_input(&mut self) {
+        let textarea = &self.app.chat.textarea;
+        self.click(textarea.x + 1, textarea.y + 1);
+    }
+
+    /// turns the mouse wheel `n` notches up
+    pub fn wheel_up(&mut self, n: usize) {
+        for _ in 0..n {
+            self.mouse(MouseEventKind::ScrollUp, 0, 0);
+        }
+    }
+
+    /// turns the mouse wheel `n` notches down
+    pub fn wheel_down(&mut self, n: usize) {
+        for _ in 0..n {
+            self.mouse(MouseEventKind::ScrollDown, 0, 0);
+        }
+    }
+
+    pub fn resize(&mut self, width: u16, height: u16) {
+        self.term.backend_mut().resize(width, height);
+        self.event(Event::Resize(width, height));
+    }
+
+    /// streams `token` into the current reply
+    pub fn stream(&mut self, token: &str) {
+        self.reply(Ok(ChatEvent::Delta(token.to_string())));
+    }
+
+    /// ends the current reply with a request to call `calls`
+    pub fn call_tools(&mut self, calls: Vec<ToolCall>) {
+        self.reply(Ok(ChatEvent::ToolCalls(calls)));
+        self.finish();
+    }
+
+    /// ends the current reply
+    pub fn finish(&mut self) {
+        self.reply(Ok(ChatEvent::Done(Stats::default())));
+    }
+
+    pub fn fail(&mut self, error: client::Error) {
+        self.reply(Err(error));
+    }
+
+    fn reply(&mut self, event: client::Result<ChatEvent>) {
+        // the reply stream ends after Done and after an error
+        let _ = self.provider.current().send(event);
+        self.app.chat.receive_pending();
+        self.app.show_notifications();
+        self.draw();
+    }
+
+    /// the text of every row above the status line, without the trailing spaces
+    pub fn screen(&self) -> String {
+        let buffer = self.term.backend().buffer();
+        let area = buffer.area;
+        let mut screen = String::new();
+
+        for y in area.top()..area.bottom().saturating_sub(1) {
+            let row: String = (area.left()..area.right())
+                .map(|x| buffer[(x, y)].symbol())
+                .collect();
+            screen.push_str(row.trim_end());
+            screen.push('\n');
+        }
+
+        screen
+    }
+
+    /// the status line
+    pub fn status(&self) -> String {
+        let buffer = self.term.backend().buffer();
+        let y = buffer.area.bottom() - 1;
+        (buffer.area.left()..buffer.area.right())
+            .map(|x| buffer[(x, y)].symbol())
+            .collect::<String>()
+            .trim()
+            .to_string()
+    }
+
+    /// Compares the screen with `tests/snapshots/<name>.snap`. With `UPDATE_SNAPSHOTS` set the
+    /// file is written instead, a missing file fails otherwise.
+    pub fn assert_snapshot(&self, name: &str) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("snapshots")
+            .join(format!("{}.snap", name));
+        let screen = self.screen();
+
+        let update = env::var_os("UPDATE_SNAPSHOTS").is_some();
+        match fs::read_to_string(&path) {
+            Ok(expected) if !update => assert!(
+                expected == screen,
+                "the screen does not match {}, rerun with UPDATE_SNAPSHOTS=1 if the change is \
+                 expected\n--- expected\n{}--- actual\n{}",
+                path.display(),
+                expected,
+                screen
+            ),
+            Err(e) if !update => panic!(
+                "Failed to read the snapshot {}: {}, rerun with UPDATE_SNAPSHOTS=1 to create it\n\
+                 --- actual\n{}",
+                path.display(),
+                e,
+                screen
+            ),
+            _ => {
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(&path, &screen).unwrap();
+            }
+        }
+    }
+}
+
+impl Drop for Driver {
+    fn drop(&mut self) {
+        let path = self.app.chat.conversation.session.path();
+        let _ = fs::remove_file(tools::audit_path(&path));
+        let _ = fs::remove_file(path);
+    }
+}
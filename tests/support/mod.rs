@@ -0,0 +1,10 @@
+//! Helpers shared by the integration tests: a scripted ollama server and a headless driver of
+//! the app.
+
+#![allow(dead_code)]
+
+pub mod driver;
+mod ollama;
+
+#[allow(unused_imports)]
+pub use ollama::*;
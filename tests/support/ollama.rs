@@ -0,0 +1,366 @@
+//! An in-process ollama server answering with scripted replies, for the integration tests.
+//!
+//! `/api/chat` and `/api/generate` answer with the next `Reply` of the script, then with the
+//! default reply once the script is over. `/api/tags` lists `MODELS` and `/api/version`
+//! answers `VERSION`.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    time,
+};
+
+pub const VERSION: &str = "0.0.0-mock";
+
+/// the models listed by `/api/tags`
+pub const MODELS: &[&str] = &["llama3.2:latest", "qwen2.5-coder:7b"];
+
+/// How the server answers a request for a reply.
+#[derive(Debug, Clone)]
+pub enum Reply {
+    /// streams the tokens one line at a time, waiting `delay` before each of them
+    Stream {
+        tokens: Vec<String>,
+        delay: Duration,
+    },
+    /// streams the tokens then closes the connection before the last line
+    Disconnect { tokens: Vec<String> },
+    /// streams the tokens then ends the body cleanly, without the last line
+    Truncate { tokens: Vec<String> },
+    /// answers with `status` and an ollama error body
+    Error { status: u16, message: String },
+    /// asks to call the tools, each call is a name and its arguments
+    ToolCalls {
+        calls: Vec<(String, serde_json::Value)>,
+    },
+}
+
+impl Reply {
+    pub fn tokens(tokens: &[&str]) -> Self {
+        Reply::Stream {
+            tokens: tokens.iter().map(|token| token.to_string()).collect(),
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub fn slow(tokens: &[&str], delay: Duration) -> Self {
+        Reply::Stream {
+            tokens: tokens.iter().map(|token| token.to_string()).collect(),
+            delay,
+        }
+    }
+
+    pub fn disconnect(tokens: &[&str]) -> Self {
+        Reply::Disconnect {
+            tokens: tokens.iter().map(|token| token.to_string()).collect(),
+        }
+    }
+
+    pub fn truncate(tokens: &[&str]) -> Self {
+        Reply::Truncate {
+            tokens: tokens.iter().map(|token| token.to_string()).collect(),
+        }
+    }
+
+    pub fn tool_call(name: &str, arguments: serde_json::Value) -> Self {
+        Reply::ToolCalls {
+            calls: vec![(name.to_string(), arguments)],
+        }
+    }
+
+    pub fn error(status: u16, message: &str) -> Self {
+        Reply::Error {
+            status,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// A request received by the server.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub body: String,
+}
+
+impl Request {
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).expect("the body is not JSON")
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    script: VecDeque<Reply>,
+    requests: Vec<Request>,
+}
+
+/// The server, it stops when dropped.
+#[derive(Debug)]
+pub struct MockOllama {
+    /// like "http://127.0.0.1:12345"
+    pub host: String,
+    state: Arc<Mutex<State>>,
+    server: tokio::task::JoinHandle<()>,
+}
+
+impl MockOllama {
+    /// starts a server answering with the replies of `script`, in order
+    pub async fn start(script: Vec<Reply>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let host = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(State {
+            script: script.into(),
+            requests: Vec::new(),
+        }));
+
+        let server_state = state.clone();
+        let server = tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(serve(socket, server_state.clone()));
+            }
+        });
+
+        Self {
+            host,
+            state,
+            server,
+        }
+    }
+
+    /// the requests received so far, oldest first
+    pub fn requests(&self) -> Vec<Request> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// the requests received on `path`
+    pub fn requests_to(&self, path: &str) -> Vec<Request> {
+        self.requests()
+            .into_iter()
+            .filter(|request| request.path == path)
+            .collect()
+    }
+}
+
+impl Drop for MockOllama {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// the reply once the script is over
+fn default_reply() -> Reply {
+    Reply::tokens(&["Hello", " from", " the", " mock"])
+}
+
+async fn serve(mut socket: TcpStream, state: Arc<Mutex<State>>) {
+    let Some(request) = read_request(&mut socket).await else {
+        return;
+    };
+    state.lock().unwrap().requests.push(request.clone());
+
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/api/version") => {
+            let body = serde_json::json!({ "version": VERSION }).to_string();
+            respond(&mut socket, 200, &body).await;
+        }
+        ("GET", "/api/tags") => {
+            let models: Vec<_> = MODELS
+                .iter()
+                .map(|name| serde_json::json!({ "name": name, "size": 1 << 30 }))
+                .collect();
+            let body = serde_json::json!({ "models": models }).to_string();
+            respond(&mut socket, 200, &body).await;
+        }
+        ("POST", path @ ("/api/chat" | "/api/generate")) => {
+            let reply = state.lock().unwrap().script.pop_front();
+            let model = request.json()["model"].as_str().unwrap_or("").to_string();
+            stream_reply(
+                &mut socket,
+                reply.unwrap_or_else(default_reply),
+                path,
+                &model,
+            )
+            .await;
+        }
+        _ => respond(&mut socket, 404, "404 page not found").await,
+    }
+}
+
+/// reads the head and the body of a request, None when the client went away
+async fn read_request(socket: &mut TcpStream) -> Option<Request> {
+    let mut data = Vec::new();
+    let mut buf = [0; 4096];
+
+    let head_end = loop {
+        let n = socket.read(&mut buf).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        data.extend_from_slice(&buf[..n]);
+        if let Some(end) = data.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&data[..head_end]).to_string();
+    let mut request_line = head.lines().next()?.split(' ');
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+    let length = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+
+    while data.len() < head_end + length {
+        let n = socket.read(&mut buf).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        data.extend_from_slice(&buf[..n]);
+    }
+
+    Some(Request {
+        method,
+        path,
+        body: String::from_utf8_lossy(&data[head_end..head_end + length]).to_string(),
+    })
+}
+
+async fn respond(socket: &mut TcpStream, status: u16, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason(status),
+        body.len(),
+        body
+    );
+    let _ = socket.write_all(response.as_bytes()).await;
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        500 => "Internal Server Error",
+        _ => "Unknown",
+    }
+}
+
+/// one line of the stream of `path`, the last one carries the statistics
+fn line(path: &str, model: &str, token: &str, done: bool) -> String {
+    let mut line = if path == "/api/generate" {
+        serde_json::json!({ "model": model, "created_at": "", "response": token, "done": done })
+    } else {
+        serde_json::json!({
+            "model": model,
+            "created_at": "",
+            "message": { "role": "assistant", "content": token },
+            "done": done,
+        })
+    };
+    if done {
+        line["eval_count"] = serde_json::json!(4);
+        line["eval_duration"] = serde_json::json!(2_000_000_000u64);
+    }
+    format!("{}\n", line)
+}
+
+/// writes `data` as one chunk of a chunked body
+async fn chunk(socket: &mut TcpStream, data: &str) -> bool {
+    let chunk = format!("{:x}\r\n{}\r\n", data.len(), data);
+    socket.write_all(chunk.as_bytes()).await.is_ok() && socket.flush().await.is_ok()
+}
+
+/// the head of a streamed reply, the body is chunked
+const STREAM_HEAD: &str = "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n";
+
+/// like ollama, the calls come in one line before the statistics
+async fn stream_tool_calls(
+    socket: &mut TcpStream,
+    calls: Vec<(String, serde_json::Value)>,
+    model: &str,
+) {
+    let calls: Vec<_> = calls
+        .into_iter()
+        .map(|(name, arguments)| {
+            serde_json::json!({ "function": { "name": name, "arguments": arguments } })
+        })
+        .collect();
+    let message = serde_json::json!({
+        "model": model,
+        "created_at": "",
+        "message": { "role": "assistant", "content": "", "tool_calls": calls },
+        "done": false,
+    });
+
+    if socket.write_all(STREAM_HEAD.as_bytes()).await.is_ok()
+        && chunk(socket, &format!("{}\n", message)).await
+        && chunk(socket, &line("/api/chat", model, "", true)).await
+    {
+        let _ = socket.write_all(b"0\r\n\r\n").await;
+    }
+}
+
+async fn stream_reply(socket: &mut TcpStream, reply: Reply, path: &str, model: &str) {
+    let (tokens, delay, end) = match reply {
+        Reply::Error { status, message } => {
+            let body = serde_json::json!({ "error": message }).to_string();
+            respond(socket, status, &body).await;
+            return;
+        }
+        Reply::Stream { tokens, delay } => (tokens, delay, End::Done),
+        Reply::Disconnect { tokens } => (tokens, Duration::ZERO, End::Drop),
+        Reply::Truncate { tokens } => (tokens, Duration::ZERO, End::Close),
+        Reply::ToolCalls { calls } => {
+            stream_tool_calls(socket, calls, model).await;
+            return;
+        }
+    };
+
+    if socket.write_all(STREAM_HEAD.as_bytes()).await.is_err() {
+        return;
+    }
+
+    for token in tokens {
+        time::sleep(delay).await;
+        if !chunk(socket, &line(path, model, &token, false)).await {
+            return;
+        }
+    }
+
+    match end {
+        End::Done => {
+            time::sleep(delay).await;
+            if chunk(socket, &line(path, model, "", true)).await {
+                let _ = socket.write_all(b"0\r\n\r\n").await;
+            }
+        }
+        // the chunked body is never terminated, the client sees the connection drop
+        End::Drop => {
+            let _ = socket.shutdown().await;
+        }
+        End::Close => {
+            let _ = socket.write_all(b"0\r\n\r\n").await;
+        }
+    }
+}
+
+/// how a streamed reply ends
+enum End {
+    /// with the last line, `done` set
+    Done,
+    /// in the middle of the chunked body
+    Drop,
+    /// with the end of the chunked body but without the last line
+    Close,
+}
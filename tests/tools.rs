@@ -0,0 +1,178 @@
+use ink::{
+    client::ToolCall,
+    config::Config,
+    tools::{Policy, Sandbox},
+};
+use serde_json::json;
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+/// a sandbox rooted in a new temporary directory, with a file and a hidden file
+fn sandbox() -> Sandbox {
+    let dir = std::env::temp_dir().join(format!("ink-sandbox-{}", Uuid::new_v4()));
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(
+        dir.join("src").join("main.rs"),
+        "fn main() {\n    todo!()\n}\n",
+    )
+    .unwrap();
+    fs::write(dir.join(".env"), "TOKEN=todo!()\n").unwrap();
+    Sandbox {
+        root: dir.canonicalize().unwrap(),
+        ..Sandbox::default()
+    }
+}
+
+fn remove(sandbox: Sandbox) {
+    fs::remove_dir_all(sandbox.root).unwrap();
+}
+
+fn call(name: &str, arguments: serde_json::Value) -> ToolCall {
+    ToolCall::new(name, arguments)
+}
+
+#[test]
+fn resolves_the_paths_in_the_root() {
+    let sandbox = sandbox();
+
+    assert_eq!(
+        sandbox.resolve("src/../src/main.rs").unwrap(),
+        sandbox.root.join("src").join("main.rs")
+    );
+    let outside = sandbox.resolve("..").unwrap_err();
+    assert!(
+        outside.contains("outside of the working directory"),
+        "{}",
+        outside
+    );
+    assert!(sandbox.resolve("/etc").is_err());
+    remove(sandbox);
+}
+
+#[test]
+fn searches_the_files_relative_to_the_root() {
+    let sandbox = sandbox();
+
+    let output = sandbox
+        .run(&call("grep", json!({ "pattern": "todo!" })))
+        .unwrap();
+    // the hidden files are skipped
+    assert_eq!(
+        output,
+        format!("src{}main.rs:2:     todo!()", std::path::MAIN_SEPARATOR)
+    );
+    remove(sandbox);
+}
+
+#[test]
+fn cuts_the_output_past_the_cap() {
+    let mut sandbox = sandbox();
+    sandbox.max_output = 10;
+
+    let output = sandbox
+        .run(&call("read_file", json!({ "path": "src/main.rs" })))
+        .unwrap();
+    assert_eq!(output, "fn main() \n... output truncated, 16 more bytes");
+    remove(sandbox);
+}
+
+#[cfg(unix)]
+#[test]
+fn reads_no_more_than_the_cap_of_an_endless_file() {
+    let sandbox = Sandbox {
+        root: PathBuf::from("/dev"),
+        max_output: 4,
+        ..Sandbox::default()
+    };
+
+    let output = sandbox
+        .run(&call("read_file", json!({ "path": "zero" })))
+        .unwrap();
+    assert_eq!(output, "\0\0\0\0\n... output truncated");
+}
+
+#[test]
+fn fails_on_missing_arguments_and_unknown_tools() {
+    let sandbox = sandbox();
+
+    assert_eq!(
+        sandbox.run(&call("read_file", json!({}))),
+        Err(String::from("Missing argument \"path\""))
+    );
+    assert_eq!(
+        sandbox.run(&call("format_disk", json!({}))),
+        Err(String::from("there is no tool named format_disk"))
+    );
+    remove(sandbox);
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_the_commands_in_the_root() {
+    let sandbox = sandbox();
+
+    let output = sandbox
+        .run(&call("run_shell", json!({ "command": "ls src; exit 3" })))
+        .unwrap();
+    assert_eq!(output, "main.rs\n\nexit code: 3");
+    remove(sandbox);
+}
+
+#[cfg(unix)]
+#[test]
+fn stops_a_command_after_the_timeout() {
+    let mut sandbox = sandbox();
+    sandbox.timeout = Duration::from_millis(300);
+
+    let started = Instant::now();
+    let output = sandbox
+        .run(&call(
+            "run_shell",
+            json!({ "command": "echo started; sleep 10" }),
+        ))
+        .unwrap();
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(output, "started\n\nThe command was stopped after 0.3s");
+    remove(sandbox);
+}
+
+#[test]
+fn asks_before_running_commands_by_default() {
+    let sandbox = Sandbox::default();
+
+    assert_eq!(sandbox.policy("read_file"), Policy::Allow);
+    assert_eq!(sandbox.policy("run_shell"), Policy::Ask);
+}
+
+#[test]
+fn reads_the_tools_section_of_the_config() {
+    let root = std::env::temp_dir().canonicalize().unwrap();
+    let config = Config::parse(&format!(
+        "[tools]\nroot = {:?}\ntimeout = 5\nmax_output = 100\n\n[tools.policy]\nrun_shell = \"deny\"\n",
+        root
+    ))
+    .unwrap();
+
+    let sandbox = config.tools.sandbox().unwrap();
+    assert_eq!(sandbox.root, root);
+    assert_eq!(sandbox.timeout, Duration::from_secs(5));
+    assert_eq!(sandbox.max_output, 100);
+    assert_eq!(sandbox.policy("run_shell"), Policy::Deny);
+
+    let config = Config::parse("[tools.policy]\ngit__commit = \"deny\"").unwrap();
+    let sandbox = config.tools.sandbox().unwrap();
+    assert_eq!(sandbox.policies["git__commit"], Policy::Deny);
+
+    let config = Config::parse("[tools.policy]\nformat_disk = \"allow\"").unwrap();
+    assert_eq!(
+        config.tools.sandbox().unwrap_err(),
+        "Unknown tool format_disk in [tools.policy]"
+    );
+    let missing = PathBuf::from("/no/such/directory");
+    let config = Config::parse(&format!("[tools]\nroot = {:?}", missing)).unwrap();
+    assert!(config.tools.sandbox().is_err());
+}
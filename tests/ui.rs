@@ -0,0 +1,265 @@
+mod support;
+
+use ink::{client::ToolCall, conversation::Role};
+use ratatui::crossterm::event::{KeyCode, KeyModifiers};
+use support::driver::Driver;
+
+fn short_conversation() -> Vec<(Role, &'static str)> {
+    vec![
+        (Role::User, "What is the capital of France?"),
+        (Role::Assistant, "The capital of France is Paris."),
+        (Role::User, "And of Italy?"),
+        (Role::Assistant, "Rome."),
+    ]
+}
+
+/// numbered prompts and replies taller than the screen
+fn long_conversation() -> Vec<(Role, String)> {
+    (1..=6)
+        .flat_map(|n| {
+            [
+                (Role::User, format!("Question {}", n)),
+                (
+                    Role::Assistant,
+                    (1..=4)
+                        .map(|line| format!("Answer {} line {}", n, line))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                ),
+            ]
+        })
+        .collect()
+}
+
+fn driver(width: u16, height: u16, messages: &[(Role, String)]) -> Driver {
+    let messages: Vec<(Role, &str)> = messages
+        .iter()
+        .map(|(role, text)| (*role, text.as_str()))
+        .collect();
+    Driver::new(width, height, &messages)
+}
+
+#[tokio::test]
+async fn renders_a_conversation() {
+    let driver = Driver::new(60, 20, &short_conversation());
+
+    driver.assert_snapshot("conversation");
+}
+
+#[tokio::test]
+async fn starts_at_the_bottom_of_a_long_conversation() {
+    let driver = driver(50, 20, &long_conversation());
+
+    assert!(driver.screen().contains("Answer 6 line 4"));
+    driver.assert_snapshot("long_conversation_bottom");
+}
+
+#[tokio::test]
+async fn clips_the_messages_scrolled_out_at_the_top() {
+    let mut driver = driver(50, 20, &long_conversation());
+
+    driver.wheel_up(3);
+    driver.assert_snapshot("long_conversation_scrolled_3");
+
+    driver.wheel_up(4);
+    driver.assert_snapshot("long_conversation_scrolled_7");
+}
+
+#[tokio::test]
+async fn scrolls_back_to_the_bottom() {
+    let mut driver = driver(50, 20, &long_conversation());
+    let bottom = driver.screen();
+
+    driver.wheel_up(100);
+    assert!(driver.screen().contains("Question 1"));
+    driver.assert_snapshot("long_conversation_top");
+
+    driver.key(KeyCode::Esc);
+    driver.key(KeyCode::End);
+    assert_eq!(driver.screen(), bottom);
+}
+
+#[tokio::test]
+async fn selects_the_messages_with_tab() {
+    let mut driver = Driver::new(60, 20, &short_conversation());
+    let path = driver.app.chat.conversation.session.tree.active_path();
+
+    assert_eq!(driver.app.chat.selected_message(), None);
+    assert!(!driver.app.chat.textarea.is_selected);
+
+    driver.key(KeyCode::Tab);
+    assert_eq!(driver.app.chat.selected_message(), Some(path[0]));
+    driver.key(KeyCode::Tab);
+    driver.key(KeyCode::Tab);
+    assert_eq!(driver.app.chat.selected_message(), Some(path[2]));
+    assert!(driver.app.chat.views[&path[2]].is_selected);
+    assert!(!driver.app.chat.views[&path[0]].is_selected);
+
+    driver.key_with(KeyCode::BackTab, KeyModifiers::SHIFT);
+    assert_eq!(driver.app.chat.selected_message(), Some(path[1]));
+}
+
+#[tokio::test]
+async fn moves_the_selection_to_the_next_message_when_it_scrolls_out() {
+    let mut driver = driver(50, 20, &long_conversation());
+    let path = driver.app.chat.conversation.session.tree.active_path();
+
+    driver.wheel_up(100);
+    driver.key(KeyCode::Tab);
+    assert_eq!(driver.app.chat.selected_message(), Some(path[0]));
+
+    // one line at a time, the first prompt leaves the screen at the top
+    driver.wheel_down(3);
+    assert!(!driver.screen().contains("Question 1"));
+    assert_eq!(driver.app.chat.selected_message(), Some(path[1]));
+    assert!(driver.app.chat.views[&path[1]].is_selected);
+    driver.assert_snapshot("selection_scrolled_out");
+
+    // the selection keeps following, the actions apply to the visible message
+    driver.wheel_down(6);
+    assert_eq!(driver.app.chat.selected_message(), Some(path[2]));
+    driver.key(KeyCode::Tab);
+    assert_eq!(driver.app.chat.selected_message(), Some(path[3]));
+}
+
+#[tokio::test]
+async fn selects_the_last_message_with_shift_tab() {
+    let mut driver = Driver::new(60, 20, &short_conversation());
+    let path = driver.app.chat.conversation.session.tree.active_path();
+
+    driver.key_with(KeyCode::BackTab, KeyModifiers::SHIFT);
+    assert_eq!(driver.app.chat.selected_message(), Some(path[3]));
+    driver.key_with(KeyCode::BackTab, KeyModifiers::SHIFT);
+    assert_eq!(driver.app.chat.selected_message(), Some(path[2]));
+}
+
+#[tokio::test]
+async fn selects_the_clicked_message() {
+    let mut driver = Driver::new(60, 20, &short_conversation());
+    let path = driver.app.chat.conversation.session.tree.active_path();
+
+    let row = driver
+        .screen()
+        .lines()
+        .position(|line| line.contains("Rome."))
+        .unwrap() as u16;
+    driver.click(5, row);
+    assert_eq!(driver.app.chat.selected_message(), Some(path[3]));
+    assert!(!driver.app.chat.textarea.is_selected);
+
+    driver.focus_input();
+    assert_eq!(driver.app.chat.selected_message(), None);
+    assert!(driver.app.chat.textarea.is_selected);
+}
+
+#[tokio::test]
+async fn fits_the_chat_to_the_new_size() {
+    let mut driver = Driver::new(60, 20, &short_conversation());
+
+    driver.resize(34, 20);
+    driver.assert_snapshot("conversation_narrow");
+
+    driver.resize(60, 17);
+    driver.assert_snapshot("conversation_short");
+
+    driver.resize(60, 20);
+    driver.assert_snapshot("conversation");
+}
+
+#[tokio::test]
+async fn shows_the_reply_while_it_streams() {
+    let mut driver = Driver::new(60, 20, &short_conversation());
+
+    driver.focus_input();
+    driver.type_text("And of Spain?");
+    driver.assert_snapshot("streaming_prompt");
+    driver.key(KeyCode::Enter);
+    assert!(driver.app.chat.conversation.generating_id.is_some());
+
+    driver.stream("The capital");
+    driver.stream(" of Spain");
+    driver.assert_snapshot("streaming_partial");
+    assert!(driver.status().contains("generating"));
+
+    driver.stream(" is Madrid.");
+    driver.finish();
+    assert!(driver.app.chat.conversation.generating_id.is_none());
+    driver.assert_snapshot("streaming_done");
+
+    let request = &driver.provider.requests()[0];
+    assert_eq!(request.messages.last().unwrap().content, "And of Spain?");
+}
+
+#[tokio::test]
+async fn follows_the_reply_until_scrolled_up() {
+    let mut driver = driver(50, 20, &long_conversation());
+
+    driver.focus_input();
+    driver.type_text("Question 7");
+    driver.key(KeyCode::Enter);
+    for line in 1..=6 {
+        driver.stream(&format!("Answer 7 line {}\n", line));
+    }
+    assert!(driver.screen().contains("Answer 7 line 6"));
+
+    driver.wheel_up(2);
+    let top = driver.screen().lines().nth(1).unwrap().to_string();
+    driver.stream("Answer 7 line 7\n");
+    driver.stream("Answer 7 line 8\n");
+    // the view stays where it was, the reply grows below the top line
+    assert_eq!(driver.screen().lines().nth(1).unwrap(), top);
+    assert!(driver.screen().contains("new content below"));
+    driver.assert_snapshot("streaming_scrolled_up");
+
+    driver.key(KeyCode::Esc);
+    driver.key(KeyCode::End);
+    driver.finish();
+    assert!(driver.screen().contains("Answer 7 line 8"));
+}
+
+#[tokio::test]
+async fn shows_a_failed_reply() {
+    let mut driver = Driver::new(60, 20, &short_conversation());
+
+    driver.focus_input();
+    driver.type_text("And of Spain?");
+    driver.key(KeyCode::Enter);
+    driver.stream("The capital");
+    driver.fail(ink::client::Error::Interrupted(String::from(
+        "connection reset",
+    )));
+
+    assert!(driver.status().starts_with("The request failed"));
+    driver.assert_snapshot("streaming_failed");
+}
+
+#[tokio::test]
+async fn asks_before_running_a_command() {
+    let mut driver = Driver::new(60, 20, &short_conversation());
+    driver.app.chat.conversation.session.settings.tools = true;
+
+    driver.focus_input();
+    driver.type_text("Delete the build");
+    driver.key(KeyCode::Enter);
+    driver.call_tools(vec![ToolCall::new(
+        "run_shell",
+        serde_json::json!({ "command": "rm -rf target" }),
+    )]);
+    assert!(driver.app.tool_dialog.is_some());
+    driver.assert_snapshot("tool_confirmation");
+
+    // the dialog takes every key until it is answered
+    driver.key(KeyCode::Tab);
+    assert!(driver.app.tool_dialog.is_some());
+    driver.key(KeyCode::Char('n'));
+    assert!(driver.app.tool_dialog.is_none());
+
+    let requests = driver.provider.requests();
+    assert_eq!(requests.len(), 2);
+    let result = requests[1].messages.last().unwrap();
+    assert_eq!(result.tool_name.as_deref(), Some("run_shell"));
+    assert_eq!(result.content, "Error: the user refused to run run_shell");
+    driver.stream("Ok, I left it.");
+    driver.finish();
+    driver.assert_snapshot("tool_refused");
+}
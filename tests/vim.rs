@@ -0,0 +1,176 @@
+use ink::widgets::vim::{Mode, UNNAMED_REGISTER, Vim};
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui_textarea::TextArea;
+
+/// An editor with `text` and the cursor at the start of it, in normal mode.
+struct Editor {
+    vim: Vim,
+    area: TextArea<'static>,
+}
+
+impl Editor {
+    fn new(text: &str) -> Self {
+        Self {
+            vim: Vim::default(),
+            area: TextArea::new(text.lines().map(String::from).collect()),
+        }
+    }
+
+    /// types `keys` one at a time, `\x1b` is Esc
+    fn keys(&mut self, keys: &str) -> &mut Self {
+        for c in keys.chars() {
+            let code = match c {
+                '\x1b' => KeyCode::Esc,
+                c => KeyCode::Char(c),
+            };
+            self.vim
+                .handle(KeyEvent::new(code, KeyModifiers::NONE), &mut self.area);
+        }
+        self
+    }
+
+    fn text(&self) -> String {
+        self.area.lines().join("\n")
+    }
+
+    fn cursor(&self) -> (usize, usize) {
+        self.area.cursor()
+    }
+
+    fn register(&self, name: char) -> String {
+        self.vim
+            .register(name)
+            .map(|register| register.text.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[test]
+fn moves_with_the_motions() {
+    let mut editor = Editor::new("hello world.\nsecond line");
+
+    assert_eq!(editor.keys("w").cursor(), (0, 6));
+    assert_eq!(editor.keys("e").cursor(), (0, 10));
+    assert_eq!(editor.keys("w").cursor(), (0, 11));
+    assert_eq!(editor.keys("b").cursor(), (0, 6));
+    assert_eq!(editor.keys("$").cursor(), (0, 12));
+    assert_eq!(editor.keys("0").cursor(), (0, 0));
+    assert_eq!(editor.keys("j").cursor(), (1, 0));
+    assert_eq!(editor.keys("gg").cursor(), (0, 0));
+    assert_eq!(editor.keys("G").cursor(), (1, 0));
+    // h and l stay on the line
+    assert_eq!(editor.keys("h").cursor(), (1, 0));
+    assert_eq!(editor.keys("$ll").cursor(), (1, 11));
+}
+
+#[test]
+fn deletes_and_yanks_with_the_operators() {
+    let mut editor = Editor::new("one two three");
+    editor.keys("dw");
+    assert_eq!(editor.text(), "two three");
+    assert_eq!(editor.register(UNNAMED_REGISTER), "one ");
+
+    editor.keys("de");
+    assert_eq!(editor.text(), " three");
+    editor.keys("x");
+    assert_eq!(editor.text(), "three");
+    editor.keys("lD");
+    assert_eq!(editor.text(), "t");
+
+    let mut editor = Editor::new("first\nsecond\nthird");
+    editor.keys("yyjp");
+    assert_eq!(editor.text(), "first\nsecond\nfirst\nthird");
+    editor.keys("ggdj");
+    assert_eq!(editor.text(), "first\nthird");
+    assert!(editor.vim.register(UNNAMED_REGISTER).unwrap().linewise);
+    editor.keys("Gdd");
+    assert_eq!(editor.text(), "first");
+
+    editor.keys("u");
+    assert_eq!(editor.text(), "first\nthird");
+}
+
+#[test]
+fn changes_a_word_up_to_its_end() {
+    let mut editor = Editor::new("hello world");
+    editor.keys("cwbye\x1b");
+    assert_eq!(editor.text(), "bye world");
+    assert_eq!(editor.vim.mode, Mode::Normal);
+
+    // on the last character of a word only that character is changed
+    let mut editor = Editor::new("hello world");
+    editor.keys("ecwX\x1b");
+    assert_eq!(editor.text(), "hellX world");
+
+    // on spaces, cw changes them like dw
+    let mut editor = Editor::new("a   b");
+    editor.keys("lcw-\x1b");
+    assert_eq!(editor.text(), "a-b");
+}
+
+#[test]
+fn selects_words_and_quotes_with_text_objects() {
+    let mut editor = Editor::new("say hello there");
+    editor.keys("wdiw");
+    assert_eq!(editor.text(), "say  there");
+
+    let mut editor = Editor::new("say hello there");
+    editor.keys("wdaw");
+    assert_eq!(editor.text(), "say there");
+
+    let mut editor = Editor::new("say \"hi there\" now");
+    editor.keys("wlci\"yo\x1b");
+    assert_eq!(editor.text(), "say \"yo\" now");
+
+    let mut editor = Editor::new("say \"hi\" now");
+    editor.keys("wlda\"");
+    assert_eq!(editor.text(), "say  now");
+}
+
+#[test]
+fn selects_the_brackets_around_the_cursor() {
+    let mut editor = Editor::new("f(a, (b), c)");
+    editor.keys("llllll");
+    assert_eq!(editor.cursor(), (0, 6));
+    editor.keys("di(");
+    assert_eq!(editor.text(), "f(a, (), c)");
+
+    let mut editor = Editor::new("f(a, (b), c)");
+    editor.keys("$da(");
+    assert_eq!(editor.text(), "f");
+
+    let mut editor = Editor::new("if {\n  x\n}");
+    editor.keys("jdiB");
+    assert_eq!(editor.text(), "if {}");
+
+    // an empty line is not inside the brackets of the next line
+    let mut editor = Editor::new("\n(x)");
+    editor.keys("di(");
+    assert_eq!(editor.text(), "\n(x)");
+}
+
+#[test]
+fn keeps_the_text_of_the_chosen_register() {
+    let mut editor = Editor::new("one two");
+    editor.keys("\"ayiwwdiw");
+    assert_eq!(editor.register('a'), "one");
+    assert_eq!(editor.register(UNNAMED_REGISTER), "two");
+
+    editor.keys("\"aP");
+    assert_eq!(editor.text(), "one one");
+}
+
+#[test]
+fn operates_on_the_visual_selection() {
+    let mut editor = Editor::new("hello world");
+    editor.keys("vey");
+    assert_eq!(editor.register(UNNAMED_REGISTER), "hello");
+    assert_eq!(editor.vim.mode, Mode::Normal);
+
+    editor.keys("wvld");
+    assert_eq!(editor.text(), "hello rld");
+
+    editor.keys("v\x1b");
+    assert_eq!(editor.vim.mode, Mode::Normal);
+    assert!(!editor.vim.captures_esc());
+}